@@ -2,7 +2,7 @@
 use std::collections::HashMap;
 
 /// 神经网络标量参数
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum Arg {
     Dim(Dim),
     Bool(bool),
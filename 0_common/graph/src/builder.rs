@@ -0,0 +1,108 @@
+use crate::{GraphTopo, TopoError, TopoNode};
+use std::ops::Range;
+
+/// 按拓扑序逐个添加节点来构造 [`GraphTopo`]，每一步都检查图结构。
+///
+/// ```rust
+/// # use graph::{TopoBuilder, TopoInput::*};
+/// let mut builder = TopoBuilder::new(1);
+/// let x = builder.push([Edge(0), Local], 1).unwrap();
+/// let y = builder.push([Edge(x.start), Edge(0)], 2).unwrap();
+/// let topo = builder.build([y.start]).unwrap();
+/// assert_eq!(topo.n_edge(), 5);
+/// ```
+pub struct TopoBuilder {
+    n_inputs: usize,
+    n_edge: usize,
+    connections: Vec<usize>,
+    nodes: Vec<TopoNode>,
+}
+
+/// 添加节点时的输入。
+#[derive(Clone, Copy, Debug)]
+pub enum TopoInput {
+    /// 已经定义的边。
+    Edge(usize),
+    /// 为此节点分配一条新的局部边，即首次使用的外部张量。
+    Local,
+}
+
+impl TopoBuilder {
+    pub fn new(n_inputs: usize) -> Self {
+        Self {
+            n_inputs,
+            n_edge: n_inputs,
+            connections: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// 已经定义的边数。
+    pub fn n_edge(&self) -> usize {
+        self.n_edge
+    }
+
+    /// 添加节点，返回其输出边的序号范围。
+    ///
+    /// 出错时构造器不会被修改。
+    pub fn push(
+        &mut self,
+        inputs: impl IntoIterator<Item = TopoInput>,
+        n_outputs: usize,
+    ) -> Result<Range<usize>, TopoError> {
+        let node = self.nodes.len();
+        let start = self.connections.len();
+        let mut n_local = 0;
+        for (slot, input) in inputs.into_iter().enumerate() {
+            let edge = match input {
+                TopoInput::Edge(edge) if edge < self.n_edge => edge,
+                TopoInput::Edge(edge) => {
+                    self.connections.truncate(start);
+                    return Err(TopoError::UseBeforeDefine { node, slot, edge });
+                }
+                TopoInput::Local => {
+                    n_local += 1;
+                    self.n_edge + n_local - 1
+                }
+            };
+            self.connections.push(edge)
+        }
+
+        self.nodes.push(TopoNode {
+            n_local,
+            n_inputs: self.connections.len() - start,
+            n_outputs,
+        });
+        self.n_edge += n_local;
+        let outputs = self.n_edge..self.n_edge + n_outputs;
+        self.n_edge = outputs.end;
+        Ok(outputs)
+    }
+
+    /// 设置全图输出，完成构造。
+    pub fn build(self, outputs: impl IntoIterator<Item = usize>) -> Result<GraphTopo, TopoError> {
+        let Self {
+            n_inputs,
+            n_edge,
+            connections: nodes_connections,
+            nodes,
+        } = self;
+
+        let mut connections = Vec::with_capacity(nodes_connections.len());
+        for (index, edge) in outputs.into_iter().enumerate() {
+            if edge >= n_edge {
+                return Err(TopoError::OutputOutOfRange { index, edge });
+            }
+            connections.push(edge)
+        }
+        let n_outputs = connections.len();
+        connections.extend(nodes_connections);
+
+        Ok(GraphTopo {
+            n_inputs,
+            n_outputs,
+            connections: connections.into(),
+            nodes: nodes.into(),
+        })
+    }
+}
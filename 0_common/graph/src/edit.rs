@@ -0,0 +1,479 @@
+use crate::{Graph, GraphTopo, NodeRef, TopoNode};
+use std::{iter::zip, ops::Range};
+
+/// 可编辑的图。
+///
+/// 以邻接表的形式保存节点的输入输出，并维护每条边的生产者和消费者。
+/// 节点和边的序号在编辑过程中保持稳定，删除的节点留空，新增的节点和边追加到末尾，
+/// 转换回 [`Graph`] 时重新拓扑排序，不再被使用的边被丢弃。
+///
+/// 没有生产者的边只能是全图输入或局部边，局部边来自原图，或通过 [`add_local`](Self::add_local) 添加。
+pub struct EditGraph<N, E> {
+    n_inputs: usize,
+    global_outputs: Vec<usize>,
+    nodes: Vec<Option<EditNode<N>>>,
+    edges: Vec<E>,
+    producers: Vec<Option<usize>>,
+    consumers: Vec<Vec<usize>>,
+    locals: Vec<bool>,
+}
+
+/// 可编辑图转换回 [`Graph`] 时发现的错误。
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EditError {
+    /// 节点在一个环上，无法拓扑排序。
+    Cycle { node: usize },
+    /// 节点的第 `slot` 个输入没有生产者，也不是全图输入或局部边，通常是生产者被删除后没有重定向。
+    Dangling {
+        node: usize,
+        slot: usize,
+        edge: usize,
+    },
+    /// 给定的顺序中节点的第 `slot` 个输入在其生产者之前使用。
+    UseBeforeDefine {
+        node: usize,
+        slot: usize,
+        edge: usize,
+    },
+    /// 给定的顺序中缺少、重复或包含已删除的节点。
+    NodeOrder { node: usize },
+    /// 第 `index` 个全图输出既不是全图输入也没有生产者。
+    OutputNotProduced { index: usize, edge: usize },
+}
+
+/// 可编辑图中的节点。
+pub struct EditNode<N> {
+    pub node: N,
+    pub inputs: Vec<usize>,
+    pub outputs: Vec<usize>,
+}
+
+impl<N, E> From<Graph<N, E>> for EditGraph<N, E> {
+    fn from(value: Graph<N, E>) -> Self {
+        let Graph { topo, nodes, edges } = value;
+
+        let mut producers = vec![None; edges.len()];
+        let mut consumers = vec![Vec::new(); edges.len()];
+        let nodes = zip(topo.iter(), nodes)
+            .enumerate()
+            .map(|(i, (topo, node))| {
+                let NodeRef { inputs, outputs } = topo;
+                for &e in inputs {
+                    consumers[e].push(i)
+                }
+                for e in outputs.clone() {
+                    producers[e] = Some(i)
+                }
+                Some(EditNode {
+                    node,
+                    inputs: inputs.to_vec(),
+                    outputs: outputs.collect(),
+                })
+            })
+            .collect();
+        // 既不是全图输入也没有生产者的是局部边
+        let locals = producers
+            .iter()
+            .enumerate()
+            .map(|(e, p)| e >= topo.n_inputs() && p.is_none())
+            .collect();
+
+        Self {
+            n_inputs: topo.n_inputs(),
+            global_outputs: topo.global_outputs().to_vec(),
+            nodes,
+            edges: edges.into_vec(),
+            producers,
+            consumers,
+            locals,
+        }
+    }
+}
+
+impl From<GraphTopo> for EditGraph<(), ()> {
+    fn from(value: GraphTopo) -> Self {
+        let nodes = vec![(); value.n_node()].into();
+        let edges = vec![(); value.n_edge()].into();
+        Graph {
+            topo: value,
+            nodes,
+            edges,
+        }
+        .into()
+    }
+}
+
+impl<N, E> EditGraph<N, E> {
+    /// 节点序号的上界，包括已删除的节点。
+    pub fn n_node(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn n_edge(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn node(&self, i: usize) -> Option<&EditNode<N>> {
+        self.nodes.get(i).and_then(Option::as_ref)
+    }
+
+    /// 获取节点的可变引用，只能修改节点本身，连接关系需要通过其他方法修改。
+    pub fn node_mut(&mut self, i: usize) -> Option<&mut N> {
+        self.nodes
+            .get_mut(i)
+            .and_then(Option::as_mut)
+            .map(|node| &mut node.node)
+    }
+
+    /// 遍历所有未删除的节点。
+    pub fn nodes(&self) -> impl Iterator<Item = (usize, &EditNode<N>)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| node.as_ref().map(|node| (i, node)))
+    }
+
+    pub fn edge(&self, e: usize) -> &E {
+        &self.edges[e]
+    }
+
+    pub fn edge_mut(&mut self, e: usize) -> &mut E {
+        &mut self.edges[e]
+    }
+
+    pub fn global_inputs(&self) -> Range<usize> {
+        0..self.n_inputs
+    }
+
+    pub fn global_outputs(&self) -> &[usize] {
+        &self.global_outputs
+    }
+
+    /// 产生边 `e` 的节点，全图输入和局部边没有生产者。
+    ///
+    /// 生产者被删除的边也没有生产者，但在转换回 [`Graph`] 前必须不再被使用。
+    pub fn producer(&self, e: usize) -> Option<usize> {
+        self.producers[e]
+    }
+
+    /// 使用边 `e` 的节点，一个节点多次使用同一条边时重复出现。
+    pub fn consumers(&self, e: usize) -> &[usize] {
+        &self.consumers[e]
+    }
+
+    /// 边 `e` 是否被图外部观察到，即是否是全图输出。
+    pub fn is_global_output(&self, e: usize) -> bool {
+        self.global_outputs.contains(&e)
+    }
+
+    /// 边 `e` 是否是局部边，即可以在没有生产者时使用。
+    pub fn is_local(&self, e: usize) -> bool {
+        self.locals[e]
+    }
+
+    /// 添加一条边，返回边序号，这条边需要作为插入的节点的输出。
+    pub fn add_edge(&mut self, edge: E) -> usize {
+        self.push_edge(edge, false)
+    }
+
+    /// 添加一条局部边，例如权重，返回边序号，这条边没有生产者。
+    pub fn add_local(&mut self, edge: E) -> usize {
+        self.push_edge(edge, true)
+    }
+
+    fn push_edge(&mut self, edge: E, local: bool) -> usize {
+        self.producers.push(None);
+        self.consumers.push(Vec::new());
+        self.locals.push(local);
+        self.edges.push(edge);
+        self.edges.len() - 1
+    }
+
+    /// 插入节点，返回节点序号。
+    ///
+    /// # Panics
+    ///
+    /// 输出边已经有生产者时 panic。
+    pub fn insert(&mut self, node: N, inputs: Vec<usize>, outputs: Vec<usize>) -> usize {
+        let i = self.nodes.len();
+        for &e in &outputs {
+            assert!(
+                self.producers[e].is_none(),
+                "edge {e} already has a producer"
+            );
+        }
+        for &e in &inputs {
+            self.consumers[e].push(i)
+        }
+        for &e in &outputs {
+            self.producers[e] = Some(i)
+        }
+        self.nodes.push(Some(EditNode {
+            node,
+            inputs,
+            outputs,
+        }));
+        i
+    }
+
+    /// 删除节点，其输出边失去生产者。
+    pub fn remove(&mut self, i: usize) -> Option<EditNode<N>> {
+        let node = self.nodes.get_mut(i)?.take()?;
+        for &e in &node.inputs {
+            let consumers = &mut self.consumers[e];
+            if let Some(pos) = consumers.iter().position(|&c| c == i) {
+                consumers.swap_remove(pos);
+            }
+        }
+        for &e in &node.outputs {
+            self.producers[e] = None
+        }
+        Some(node)
+    }
+
+    /// 将节点 `i` 的第 `slot` 个输入替换为边 `e`，返回原来的输入边。
+    pub fn set_input(&mut self, i: usize, slot: usize, e: usize) -> usize {
+        let old = std::mem::replace(&mut self.nodes[i].as_mut().unwrap().inputs[slot], e);
+        let consumers = &mut self.consumers[old];
+        let pos = consumers.iter().position(|&c| c == i).unwrap();
+        consumers.swap_remove(pos);
+        self.consumers[e].push(i);
+        old
+    }
+
+    /// 将节点 `i` 的第 `slot` 个输出替换为边 `e`，返回原来的输出边，原来的输出边失去生产者。
+    ///
+    /// # Panics
+    ///
+    /// 边 `e` 已经有生产者时 panic。
+    pub fn set_output(&mut self, i: usize, slot: usize, e: usize) -> usize {
+        assert!(
+            self.producers[e].is_none(),
+            "edge {e} already has a producer"
+        );
+        let old = std::mem::replace(&mut self.nodes[i].as_mut().unwrap().outputs[slot], e);
+        self.producers[old] = None;
+        self.producers[e] = Some(i);
+        old
+    }
+
+    /// 边 `e` 是否直接或间接依赖边 `on`，用于在重定向前检查是否会形成环。
+    pub fn depends_on(&self, e: usize, on: usize) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = self.producers[e].into_iter().collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            if std::mem::replace(&mut visited[i], true) {
+                continue;
+            }
+            let inputs = &self.nodes[i].as_ref().unwrap().inputs;
+            if inputs.contains(&on) {
+                return true;
+            }
+            stack.extend(inputs.iter().filter_map(|&e| self.producers[e]))
+        }
+        false
+    }
+
+    /// 将所有对边 `old` 的使用（包括全图输出）重定向到边 `new`。
+    ///
+    /// `new` 依赖 `old` 时重定向会形成环，调用者应该先用 [`depends_on`](Self::depends_on) 检查。
+    pub fn rewire(&mut self, old: usize, new: usize) {
+        if old == new {
+            return;
+        }
+        // 消费者列表中每一项对应一次使用，可以直接转移
+        let consumers = std::mem::take(&mut self.consumers[old]);
+        for &c in &consumers {
+            for input in &mut self.nodes[c].as_mut().unwrap().inputs {
+                if *input == old {
+                    *input = new
+                }
+            }
+        }
+        self.consumers[new].extend(consumers);
+        for output in &mut self.global_outputs {
+            if *output == old {
+                *output = new
+            }
+        }
+    }
+
+    /// 深度优先的拓扑排序，尽量保持节点的原有顺序。图中存在环时返回环上的一个节点。
+    pub fn topo_order(&self) -> Result<Vec<usize>, EditError> {
+        const UNVISITED: u8 = 0;
+        const VISITING: u8 = 1;
+        const DONE: u8 = 2;
+
+        let mut state = vec![UNVISITED; self.nodes.len()];
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack = Vec::new();
+        for (root, _) in self.nodes() {
+            if state[root] != UNVISITED {
+                continue;
+            }
+            state[root] = VISITING;
+            stack.push((root, 0));
+            while let Some(&(i, j)) = stack.last() {
+                match self.nodes[i].as_ref().unwrap().inputs.get(j) {
+                    Some(&e) => {
+                        stack.last_mut().unwrap().1 += 1;
+                        if let Some(p) = self.producers[e] {
+                            match state[p] {
+                                UNVISITED => {
+                                    state[p] = VISITING;
+                                    stack.push((p, 0))
+                                }
+                                VISITING => return Err(EditError::Cycle { node: p }),
+                                _ => {}
+                            }
+                        }
+                    }
+                    None => {
+                        state[i] = DONE;
+                        order.push(i);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// 转换回紧凑的图，节点按依赖关系重新拓扑排序。
+    ///
+    /// 局部边在首次使用时成为节点的局部边。
+    pub fn into_graph(self) -> Result<Graph<N, E>, EditError> {
+        let order = self.topo_order()?;
+        self.into_graph_in(order)
+    }
+
+    /// 检查按 `order` 转换回 [`Graph`] 时图结构是否正确，`order` 必须恰好包含所有未删除的节点，且是一个拓扑序。
+    pub fn check(&self, order: &[usize]) -> Result<(), EditError> {
+        let mut defined = vec![false; self.edges.len()];
+        defined[..self.n_inputs].fill(true);
+        let mut visited = vec![false; self.nodes.len()];
+        for &i in order {
+            let Some(node) = self.node(i).filter(|_| !visited[i]) else {
+                return Err(EditError::NodeOrder { node: i });
+            };
+            visited[i] = true;
+            for (slot, &edge) in node.inputs.iter().enumerate() {
+                if defined[edge] {
+                    continue;
+                }
+                if self.locals[edge] {
+                    defined[edge] = true;
+                    continue;
+                }
+                return Err(match self.producers[edge] {
+                    Some(_) => EditError::UseBeforeDefine {
+                        node: i,
+                        slot,
+                        edge,
+                    },
+                    None => EditError::Dangling {
+                        node: i,
+                        slot,
+                        edge,
+                    },
+                });
+            }
+            for &e in &node.outputs {
+                defined[e] = true
+            }
+        }
+        if let Some((node, _)) = self.nodes().find(|&(i, _)| !visited[i]) {
+            return Err(EditError::NodeOrder { node });
+        }
+        for (index, &edge) in self.global_outputs.iter().enumerate() {
+            if !defined[edge] {
+                return Err(EditError::OutputNotProduced { index, edge });
+            }
+        }
+        Ok(())
+    }
+
+    /// 按给定的节点顺序转换回紧凑的图，`order` 的要求见 [`check`](Self::check)。
+    pub fn into_graph_in(
+        self,
+        order: impl IntoIterator<Item = usize>,
+    ) -> Result<Graph<N, E>, EditError> {
+        let order = order.into_iter().collect::<Vec<_>>();
+        self.check(&order)?;
+
+        let Self {
+            n_inputs,
+            global_outputs,
+            mut nodes,
+            edges: old_edges,
+            ..
+        } = self;
+
+        let n_outputs = global_outputs.len();
+        let mut edge_map = vec![usize::MAX; old_edges.len()];
+        let mut old_edges = old_edges.into_iter().map(Some).collect::<Vec<_>>();
+
+        let mut topo_nodes = Vec::with_capacity(nodes.len());
+        let mut new_nodes = Vec::with_capacity(nodes.len());
+        let mut edges = Vec::with_capacity(old_edges.len());
+        let mut connections = Vec::new();
+
+        // 填入全图输入
+        for (i, map) in edge_map.iter_mut().enumerate().take(n_inputs) {
+            *map = i;
+            edges.push(old_edges[i].take().unwrap())
+        }
+        // 预留全图输出的空间
+        connections.extend(std::iter::repeat_n(usize::MAX, n_outputs));
+        // 按拓扑序遍历节点
+        for i in order {
+            let EditNode {
+                node,
+                inputs,
+                outputs,
+            } = nodes[i].take().unwrap();
+            let n_inputs = inputs.len();
+            let n_outputs = outputs.len();
+            let mut n_local = 0;
+            for e in inputs {
+                let j = match edge_map[e] {
+                    usize::MAX => {
+                        // 未映射，成为局部边
+                        let j = edges.len();
+                        edge_map[e] = j;
+                        n_local += 1;
+                        edges.push(old_edges[e].take().unwrap());
+                        j
+                    }
+                    j => j,
+                };
+                connections.push(j)
+            }
+            for e in outputs {
+                edge_map[e] = edges.len();
+                edges.push(old_edges[e].take().unwrap())
+            }
+            topo_nodes.push(TopoNode {
+                n_local,
+                n_inputs,
+                n_outputs,
+            });
+            new_nodes.push(node)
+        }
+        // 回填全图输出
+        for (i, e) in global_outputs.into_iter().enumerate() {
+            connections[i] = edge_map[e]
+        }
+
+        Ok(Graph {
+            topo: GraphTopo {
+                n_inputs,
+                n_outputs,
+                connections: connections.into(),
+                nodes: topo_nodes.into(),
+            },
+            nodes: new_nodes.into(),
+            edges: edges.into(),
+        })
+    }
+}
@@ -1,6 +1,12 @@
+mod builder;
+mod edit;
+mod query;
 mod topo;
 
-pub use topo::{GraphTopo, NodeRef, TopoNode};
+pub use builder::{TopoBuilder, TopoInput};
+pub use edit::{EditError, EditGraph, EditNode};
+pub use query::{Direction, Subgraph, TopoIndex};
+pub use topo::{GraphTopo, NodeRef, TopoError, TopoNode};
 
 #[derive(Clone)]
 pub struct Graph<N, E> {
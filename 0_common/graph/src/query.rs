@@ -0,0 +1,283 @@
+use crate::{Graph, GraphTopo, NodeRef, TopoNode};
+use std::{collections::VecDeque, ops::Range};
+
+/// 拓扑结构的索引，支持随机访问节点，以及查询边的生产者和消费者。
+pub struct TopoIndex<'a> {
+    topo: &'a GraphTopo,
+    /// 每个节点的输入在连接表中的起点，以及第一个输出边的序号。
+    offsets: Box<[(usize, usize)]>,
+    producers: Box<[Option<usize>]>,
+    /// 压缩存储的消费者表，边 `e` 的消费者为 `consumers[heads[e]..heads[e + 1]]`。
+    heads: Box<[usize]>,
+    consumers: Box<[usize]>,
+}
+
+/// 遍历方向。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    /// 从生产者到消费者。
+    Forward,
+    /// 从消费者到生产者。
+    Backward,
+}
+
+impl GraphTopo {
+    /// 建立索引，需要遍历一次图。
+    pub fn index(&self) -> TopoIndex<'_> {
+        let n_edge = self.n_edge();
+        let mut offsets = Vec::with_capacity(self.nodes.len());
+        let mut producers = vec![None; n_edge];
+        let mut heads = vec![0; n_edge + 1];
+
+        let mut i_conn = self.n_outputs;
+        let mut i_edge = self.n_inputs;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let &TopoNode {
+                n_local,
+                n_inputs,
+                n_outputs,
+            } = node;
+            i_edge += n_local;
+            offsets.push((i_conn, i_edge));
+            for &e in &self.connections[i_conn..][..n_inputs] {
+                heads[e + 1] += 1
+            }
+            producers[i_edge..][..n_outputs].fill(Some(i));
+            i_conn += n_inputs;
+            i_edge += n_outputs;
+        }
+        // 计数转换为起点
+        for e in 0..n_edge {
+            heads[e + 1] += heads[e]
+        }
+        let mut consumers = vec![0; heads[n_edge]];
+        let mut cursor = heads.clone();
+        for (i, &(i_conn, _)) in offsets.iter().enumerate() {
+            for &e in &self.connections[i_conn..][..self.nodes[i].n_inputs] {
+                consumers[cursor[e]] = i;
+                cursor[e] += 1
+            }
+        }
+
+        TopoIndex {
+            topo: self,
+            offsets: offsets.into(),
+            producers: producers.into(),
+            heads: heads.into(),
+            consumers: consumers.into(),
+        }
+    }
+}
+
+impl<'a> TopoIndex<'a> {
+    pub fn topo(&self) -> &'a GraphTopo {
+        self.topo
+    }
+
+    /// 随机访问节点。
+    pub fn node(&self, i: usize) -> NodeRef<'a> {
+        let (i_conn, i_edge) = self.offsets[i];
+        let TopoNode {
+            n_inputs,
+            n_outputs,
+            ..
+        } = self.topo.nodes[i];
+        NodeRef {
+            inputs: &self.topo.connections[i_conn..][..n_inputs],
+            outputs: i_edge..i_edge + n_outputs,
+        }
+    }
+
+    /// 节点的局部边。
+    pub fn locals(&self, i: usize) -> Range<usize> {
+        let (_, i_edge) = self.offsets[i];
+        i_edge - self.topo.nodes[i].n_local..i_edge
+    }
+
+    /// 产生边 `e` 的节点，全图输入和局部边没有生产者。
+    pub fn producer(&self, e: usize) -> Option<usize> {
+        self.producers[e]
+    }
+
+    /// 使用边 `e` 的节点，按拓扑序排列，一个节点多次使用同一条边时重复出现。
+    pub fn consumers(&self, e: usize) -> &[usize] {
+        &self.consumers[self.heads[e]..self.heads[e + 1]]
+    }
+
+    /// 节点 `i` 的直接前驱，可能重复。
+    pub fn predecessors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.node(i)
+            .inputs
+            .iter()
+            .filter_map(|&e| self.producers[e])
+    }
+
+    /// 节点 `i` 的直接后继，可能重复。
+    pub fn successors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.node(i)
+            .outputs
+            .flat_map(|e| self.consumers(e).iter().copied())
+    }
+
+    /// 按逆拓扑序遍历节点。
+    pub fn iter_rev(&self) -> impl Iterator<Item = (usize, NodeRef<'a>)> + '_ {
+        (0..self.offsets.len()).rev().map(|i| (i, self.node(i)))
+    }
+
+    /// 从 `roots` 出发广度优先遍历，返回访问到的节点，包括 `roots`。
+    pub fn bfs(&self, roots: impl IntoIterator<Item = usize>, dir: Direction) -> Vec<usize> {
+        let mut visited = vec![false; self.offsets.len()];
+        let mut queue = VecDeque::new();
+        for i in roots {
+            if !std::mem::replace(&mut visited[i], true) {
+                queue.push_back(i)
+            }
+        }
+        let mut ans = Vec::new();
+        while let Some(i) = queue.pop_front() {
+            ans.push(i);
+            self.for_each_next(i, dir, |j| {
+                if !std::mem::replace(&mut visited[j], true) {
+                    queue.push_back(j)
+                }
+            })
+        }
+        ans
+    }
+
+    /// 从 `roots` 出发深度优先遍历，按先序返回访问到的节点，包括 `roots`。
+    pub fn dfs(&self, roots: impl IntoIterator<Item = usize>, dir: Direction) -> Vec<usize> {
+        let mut visited = vec![false; self.offsets.len()];
+        let mut stack = roots.into_iter().collect::<Vec<_>>();
+        stack.reverse();
+        let mut ans = Vec::new();
+        let mut next = Vec::new();
+        while let Some(i) = stack.pop() {
+            if std::mem::replace(&mut visited[i], true) {
+                continue;
+            }
+            ans.push(i);
+            // 逆序压栈，使先出现的相邻节点先被访问
+            self.for_each_next(i, dir, |j| next.push(j));
+            stack.extend(next.drain(..).rev().filter(|&j| !visited[j]))
+        }
+        ans
+    }
+
+    fn for_each_next(&self, i: usize, dir: Direction, f: impl FnMut(usize)) {
+        match dir {
+            Direction::Forward => self.successors(i).for_each(f),
+            Direction::Backward => self.predecessors(i).for_each(f),
+        }
+    }
+}
+
+/// 从图中提取的子图。
+pub struct Subgraph<N, E> {
+    pub graph: Graph<N, E>,
+    /// 子图节点在原图中的序号，按拓扑序排列。
+    pub nodes: Box<[usize]>,
+    /// 子图的全图输入在原图中对应的边。
+    pub inputs: Box<[usize]>,
+    /// 子图的全图输出在原图中对应的边。
+    pub outputs: Box<[usize]>,
+}
+
+impl<N: Clone, E: Clone> Graph<N, E> {
+    /// 按节点集合提取子图。
+    ///
+    /// 由集合外部产生的边（包括原图的全图输入）成为子图的全图输入，
+    /// 被集合外部使用的边（包括原图的全图输出）成为子图的全图输出，
+    /// 局部边仍然是子图的局部边。
+    pub fn subgraph(&self, nodes: impl IntoIterator<Item = usize>) -> Subgraph<N, E> {
+        let index = self.topo.index();
+        let mut nodes = nodes.into_iter().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        nodes.dedup();
+
+        let mut in_set = vec![false; self.topo.n_node()];
+        for &i in &nodes {
+            in_set[i] = true
+        }
+        let is_input = |e: usize| match index.producer(e) {
+            Some(p) => !in_set[p],
+            None => e < self.topo.n_inputs,
+        };
+
+        // 收集子图输入输出
+        let mut edge_map = vec![usize::MAX; self.edges.len()];
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for &i in &nodes {
+            let NodeRef {
+                inputs: inputs_,
+                outputs: outputs_,
+            } = index.node(i);
+            for &e in inputs_ {
+                if is_input(e) && edge_map[e] == usize::MAX {
+                    edge_map[e] = inputs.len();
+                    inputs.push(e)
+                }
+            }
+            for e in outputs_ {
+                if self.topo.global_outputs().contains(&e)
+                    || index.consumers(e).iter().any(|&c| !in_set[c])
+                {
+                    outputs.push(e)
+                }
+            }
+        }
+
+        // 按原图顺序重建拓扑
+        let mut edges = inputs
+            .iter()
+            .map(|&e| self.edges[e].clone())
+            .collect::<Vec<_>>();
+        let mut connections = vec![usize::MAX; outputs.len()];
+        let mut topo_nodes = Vec::with_capacity(nodes.len());
+        for &i in &nodes {
+            let NodeRef {
+                inputs: inputs_,
+                outputs: outputs_,
+            } = index.node(i);
+            let mut n_local = 0;
+            for &e in inputs_ {
+                if edge_map[e] == usize::MAX {
+                    // 只可能是局部边
+                    edge_map[e] = edges.len();
+                    edges.push(self.edges[e].clone());
+                    n_local += 1
+                }
+                connections.push(edge_map[e])
+            }
+            for e in outputs_.clone() {
+                edge_map[e] = edges.len();
+                edges.push(self.edges[e].clone())
+            }
+            topo_nodes.push(TopoNode {
+                n_local,
+                n_inputs: inputs_.len(),
+                n_outputs: outputs_.len(),
+            })
+        }
+        for (i, &e) in outputs.iter().enumerate() {
+            connections[i] = edge_map[e]
+        }
+
+        Subgraph {
+            graph: Graph {
+                topo: GraphTopo {
+                    n_inputs: inputs.len(),
+                    n_outputs: outputs.len(),
+                    connections: connections.into(),
+                    nodes: topo_nodes.into(),
+                },
+                nodes: nodes.iter().map(|&i| self.nodes[i].clone()).collect(),
+                edges: edges.into(),
+            },
+            nodes: nodes.into(),
+            inputs: inputs.into(),
+            outputs: outputs.into(),
+        }
+    }
+}
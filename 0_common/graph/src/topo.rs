@@ -20,7 +20,46 @@ pub struct TopoNode {
     pub n_outputs: usize,
 }
 
+/// 图结构错误。
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TopoError {
+    /// 连接数与节点声明的输入数不符，此时无法继续遍历节点。
+    ConnectionCount { expected: usize, actual: usize },
+    /// 节点的第 `slot` 个输入引用了不存在的边。
+    EdgeOutOfRange {
+        node: usize,
+        slot: usize,
+        edge: usize,
+    },
+    /// 节点的第 `slot` 个输入引用了在节点之后才定义的边，包括节点自己的输出。
+    UseBeforeDefine {
+        node: usize,
+        slot: usize,
+        edge: usize,
+    },
+    /// 节点的局部边没有被节点自己使用，这条边没有生产者也不是任何节点的首次使用。
+    UnusedLocal { node: usize, edge: usize },
+    /// 第 `index` 个全图输出引用了不存在的边。
+    OutputOutOfRange { index: usize, edge: usize },
+}
+
 impl GraphTopo {
+    /// 检查图结构后构造图。
+    pub fn new(
+        n_inputs: usize,
+        n_outputs: usize,
+        connections: Box<[usize]>,
+        nodes: Box<[TopoNode]>,
+    ) -> Result<Self, Vec<TopoError>> {
+        let ans = Self {
+            n_inputs,
+            n_outputs,
+            connections,
+            nodes,
+        };
+        ans.validate().map(|()| ans)
+    }
+
     /// # Safety
     ///
     /// 调用者来保证图结构的正确性
@@ -64,6 +103,56 @@ impl GraphTopo {
         &self.connections[..self.n_outputs]
     }
 
+    /// 检查图结构，返回发现的所有错误。
+    pub fn validate(&self) -> Result<(), Vec<TopoError>> {
+        let expected = self.n_outputs + self.nodes.iter().map(|n| n.n_inputs).sum::<usize>();
+        if self.connections.len() != expected {
+            return Err(vec![TopoError::ConnectionCount {
+                expected,
+                actual: self.connections.len(),
+            }]);
+        }
+
+        let n_edge = self.n_edge();
+        let mut errors = Vec::new();
+        for (i, node) in self.iter().enumerate() {
+            let NodeRef { inputs, outputs } = node;
+            // 局部边紧接在输出之前分配
+            let locals = outputs.start - self.nodes[i].n_local..outputs.start;
+            for (slot, &edge) in inputs.iter().enumerate() {
+                if edge >= n_edge {
+                    errors.push(TopoError::EdgeOutOfRange {
+                        node: i,
+                        slot,
+                        edge,
+                    })
+                } else if edge >= outputs.start {
+                    errors.push(TopoError::UseBeforeDefine {
+                        node: i,
+                        slot,
+                        edge,
+                    })
+                }
+            }
+            for edge in locals {
+                if !inputs.contains(&edge) {
+                    errors.push(TopoError::UnusedLocal { node: i, edge })
+                }
+            }
+        }
+        for (index, &edge) in self.global_outputs().iter().enumerate() {
+            if edge >= n_edge {
+                errors.push(TopoError::OutputOutOfRange { index, edge })
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     pub fn iter(&self) -> Iter {
         Iter {
             topo: self,
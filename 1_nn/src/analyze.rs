@@ -0,0 +1,66 @@
+use crate::NNGraph;
+use std::{collections::BTreeSet, iter::zip, ops::Range};
+
+/// 动态性分析的结果，即一段依赖相同符号变量集合的连续节点。
+#[derive(Clone, Debug)]
+pub struct DynamicRegion {
+    /// 区间包含的节点序号。
+    pub nodes: Range<usize>,
+    /// 区间内节点的输入输出形状中出现的符号变量。
+    pub variables: BTreeSet<String>,
+    /// 区间内所有节点的公共命名空间前缀。
+    pub namespace: String,
+}
+
+impl DynamicRegion {
+    /// 区间内所有形状都是常量，可以整体静态化（例如捕获为设备计算图）。
+    #[inline]
+    pub fn is_static(&self) -> bool {
+        self.variables.is_empty()
+    }
+}
+
+impl<T> NNGraph<T> {
+    /// 动态性分析：按拓扑序将相邻且依赖相同符号变量集合的节点划分到同一区间。
+    pub fn dynamic_regions(&self) -> Vec<DynamicRegion> {
+        let graph::Graph { topo, nodes, edges } = &self.graph;
+
+        let mut ans = Vec::<DynamicRegion>::new();
+        for (i, (topo, node)) in zip(topo.iter(), nodes).enumerate() {
+            let mut variables = BTreeSet::new();
+            for j in topo.inputs.iter().cloned().chain(topo.outputs) {
+                for d in edges[j].meta.shape.iter() {
+                    d.append_variables(&mut variables)
+                }
+            }
+            let namespace = namespace_of(&node.name);
+            match ans.last_mut() {
+                Some(region) if region.variables.iter().eq(variables.iter().copied()) => {
+                    region.nodes.end = i + 1;
+                    region.namespace = common_namespace(&region.namespace, namespace)
+                }
+                _ => ans.push(DynamicRegion {
+                    nodes: i..i + 1,
+                    variables: variables.into_iter().map(String::from).collect(),
+                    namespace: namespace.into(),
+                }),
+            }
+        }
+        ans
+    }
+}
+
+/// 节点名字形如 `path:name`，取出其中的 `path`。
+fn namespace_of(name: &str) -> &str {
+    name.split_once(':').map_or(name, |(path, _)| path)
+}
+
+/// 按 `.` 分隔的层级计算两个命名空间的公共前缀。
+fn common_namespace(a: &str, b: &str) -> String {
+    a.split('.')
+        .zip(b.split('.'))
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect::<Vec<_>>()
+        .join(".")
+}
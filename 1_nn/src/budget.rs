@@ -0,0 +1,84 @@
+use crate::{GraphBuilder, NNError, NNGraph, Tensor, op::OpError};
+use mem::PoolConfig;
+use std::collections::{BTreeSet, HashMap};
+
+impl GraphBuilder {
+    /// 在 `1..=value[var]` 中搜索变量 `var` 的最大值，使图能按 `config` 完成分配，其他变量的值由 `value` 给出。
+    ///
+    /// 循环先展开，再对每个取值依次锁定形状、原地化、在布局不满足要求的输入前插入 rearrange，
+    /// 最后用 `config` 的策略在 `config.max_size` 字节内分配，与实际执行前的流程一致。
+    ///
+    /// 二分查找假设占用的空间随变量的值单调不减，即某个值能分配时更小的值也都能分配，取 1 也无法分配时返回 [`None`]。
+    /// `value` 缺少图中出现的变量时返回 [`OpError::ArgError`]，形状的相等约束不成立时返回 [`OpError::ShapeMismatch`]。
+    pub fn max_fit<'a, T: Clone, U>(
+        &self,
+        graph: &NNGraph<T>,
+        var: &'a str,
+        value: &HashMap<&'a str, usize>,
+        config: PoolConfig,
+        mut map: impl FnMut(T) -> Tensor<U, 2>,
+    ) -> Result<Option<usize>, NNError> {
+        let graph = graph.clone().unroll();
+
+        // 图中的变量都要有值
+        let mut variables = BTreeSet::from([var]);
+        for edge in &graph.graph.edges {
+            for d in &edge.meta.shape {
+                d.append_variables(&mut variables)
+            }
+        }
+        if let Some(missing) = variables.into_iter().find(|v| !value.contains_key(v)) {
+            return Err(NNError {
+                name: missing.into(),
+                err: OpError::ArgError,
+            });
+        }
+
+        let mut value = value.clone();
+        let mut hi = value[var];
+        let mut fits = |n: usize| {
+            value.insert(var, n);
+            check_shapes(&graph, &value)?;
+            let mut graph = graph.clone().lower(&value, &mut map);
+            self.inplace(&mut graph);
+            self.rearrange_inputs(&mut graph);
+            let PoolConfig {
+                strategy,
+                max_size,
+                alignment,
+            } = config;
+            Ok(graph
+                .mem_range_map_with(strategy, max_size, alignment)
+                .is_ok())
+        };
+
+        // 二分查找最后一个满足条件的值
+        let mut lo = 0;
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if fits(mid)? { lo = mid } else { hi = mid - 1 }
+        }
+        Ok((lo > 0).then_some(lo))
+    }
+}
+
+/// 检查所有边的形状在 `value` 下都能求值，出错时返回使用此边的第一个节点。
+fn check_shapes<T>(graph: &NNGraph<T>, value: &HashMap<&str, usize>) -> Result<(), NNError> {
+    let graph::Graph { topo, nodes, edges } = &graph.graph;
+    for (topo, node) in topo.iter().zip(nodes) {
+        for e in topo.inputs.iter().copied().chain(topo.outputs) {
+            if edges[e]
+                .meta
+                .shape
+                .iter()
+                .any(|d| d.substitute(value).is_none())
+            {
+                return Err(NNError {
+                    name: node.name.clone(),
+                    err: OpError::ShapeMismatch,
+                });
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,11 @@
 ﻿use super::{GraphBuilder, OpLib, Tensor, TensorMeta};
-use crate::{Arg, Dim, Edge, NNError, NNGraph, NuralNetwork, ctx::name::Namespace, op::OpError};
-use graph::{GraphTopo, TopoNode};
+use crate::{
+    Arg, Bindings, Body, Dim, Edge, NNError, NNGraph, NuralNetwork, ctx::name::Namespace,
+    op::OpError,
+};
+use graph::{GraphTopo, NodeRef, TopoNode};
 use mem::{External, Node, Operator};
-use std::{cell::RefCell, collections::HashMap, fmt::Display, ops::Range, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, iter::zip, ops::Range, rc::Rc};
 use tensor::digit_layout::DigitLayout;
 
 pub struct Context<T>(Rc<RefCell<Internal<T>>>);
@@ -13,42 +16,46 @@ impl GraphBuilder {
         nn: NN,
         inputs: impl IntoIterator<Item = TensorMeta>,
     ) -> Result<NNGraph<T>, NNError> {
-        let (ctx, inputs) = self.new_context(inputs);
+        let (ctx, inputs) = new_context(self.op_lib.clone(), "Ω", self.fold_repeat, inputs);
         let outputs = nn.launch(inputs, ctx.clone()).map(|(_, outputs)| outputs)?;
         Ok(ctx.into_graph(outputs))
     }
+}
 
-    fn new_context<T>(
-        &self,
-        global_inputs: impl IntoIterator<Item = TensorMeta>,
-    ) -> (Context<T>, Vec<Tensor<T>>) {
-        let tensors = global_inputs
-            .into_iter()
-            .enumerate()
-            .map(|(i, meta)| Tensor_ {
-                name: format!("Ω.{i}"),
-                meta,
-                external: None,
-            })
-            .collect::<Vec<_>>();
-        let n_inputs = tensors.len();
-        let ctx = Context(Rc::new(RefCell::new(Internal {
-            op_lib: self.op_lib.clone(),
-            namespace: Namespace::new("Ω"),
-            operators: Default::default(),
-            tensors,
-            n_inputs,
-        })));
+fn new_context<T>(
+    op_lib: Rc<OpLib>,
+    root: impl Display,
+    fold_repeat: bool,
+    global_inputs: impl IntoIterator<Item = TensorMeta>,
+) -> (Context<T>, Vec<Tensor<T>>) {
+    let tensors = global_inputs
+        .into_iter()
+        .enumerate()
+        .map(|(i, meta)| Tensor_ {
+            name: format!("{root}.{i}"),
+            meta,
+            external: None,
+        })
+        .collect::<Vec<_>>();
+    let n_inputs = tensors.len();
+    let ctx = Context(Rc::new(RefCell::new(Internal {
+        op_lib,
+        namespace: Namespace::new(root),
+        operators: Default::default(),
+        tensors,
+        n_inputs,
+        fold_repeat,
+        bodies: Default::default(),
+    })));
 
-        let tensors = (0..n_inputs)
-            .map(|idx| Tensor {
-                idx,
-                ctx: ctx.clone(),
-            })
-            .collect();
+    let tensors = (0..n_inputs)
+        .map(|idx| Tensor {
+            idx,
+            ctx: ctx.clone(),
+        })
+        .collect();
 
-        (ctx, tensors)
-    }
+    (ctx, tensors)
 }
 
 pub(super) struct Internal<T> {
@@ -57,6 +64,8 @@ pub(super) struct Internal<T> {
     operators: Vec<Op_>,
     tensors: Vec<Tensor_<T>>,
     n_inputs: usize,
+    fold_repeat: bool,
+    bodies: Vec<Body<T>>,
 }
 
 struct Op_ {
@@ -162,6 +171,132 @@ impl<T> Context<T> {
             Err(err) => return Err(NNError { name, err }),
         };
 
+        drop(internal);
+        Ok(self.push_op(name, Operator { name: op, arg }, inputs, meta))
+    }
+
+    /// 依次将 `carried` 和 `shared` 输入每个网络，每个网络的输出作为下一个网络的 `carried`。
+    ///
+    /// 默认逐层展开，第 `i` 个网络的命名空间为 `{name}{i}`。
+    /// 构造器设置了 [`fold_repeat`](super::GraphBuilder::fold_repeat) 时，
+    /// 所有网络结构相同则构造为一个 `repeat` 节点，只保存一份循环体以及每次迭代绑定的外部张量，
+    /// 否则仍然逐层展开。
+    pub fn repeat<NN: NuralNetwork<T>>(
+        &mut self,
+        name: impl Display,
+        nns: impl IntoIterator<Item = NN>,
+        carried: impl IntoIterator<Item = Tensor<T>>,
+        shared: impl IntoIterator<Item = Tensor<T>>,
+    ) -> Result<Vec<Tensor<T>>, NNError> {
+        let mut carried = carried.into_iter().collect::<Vec<_>>();
+        let shared = shared.into_iter().collect::<Vec<_>>();
+
+        if !self.0.borrow().fold_repeat {
+            for (i, nn) in nns.into_iter().enumerate() {
+                let inputs = carried.into_iter().chain(shared.iter().cloned());
+                carried = self.trap(format!("{name}{i}"), nn, inputs)?
+            }
+            return Ok(carried);
+        }
+
+        let (op_lib, namespace, node_name) = {
+            let mut internal = self.0.borrow_mut();
+            let op_lib = internal.op_lib.clone();
+            let top = internal.namespace.top_mut();
+            let name = top.sub_nn.decorate(name.to_string());
+            let namespace = format!("{}.{name}", top.path());
+            let node_name = top.operator.decorate(name);
+            let node_name = format!("{}:{node_name}", top.path());
+            (op_lib, namespace, node_name)
+        };
+        let inputs = carried
+            .iter()
+            .chain(&shared)
+            .map(|t| t.idx)
+            .collect::<Box<_>>();
+        let meta = inputs
+            .iter()
+            .map(|&idx| self.get_meta(idx))
+            .collect::<Vec<_>>();
+
+        // 每个网络单独构造
+        let mut layers = Vec::new();
+        for (i, nn) in nns.into_iter().enumerate() {
+            let root = format!("{namespace}{i}");
+            let (ctx, inputs) = new_context(op_lib.clone(), &root, false, meta.clone());
+            let outputs = nn.launch(inputs, ctx.clone()).map(|(_, outputs)| outputs)?;
+            layers.push(ctx.into_graph(outputs))
+        }
+        let Some(body) = layers.first() else {
+            return Ok(carried);
+        };
+
+        // 结构不同，或循环变量在每次迭代前后的形状不同时不能折叠，逐层展开
+        let graph::Graph { topo, edges, .. } = &body.graph;
+        let outputs = topo
+            .global_outputs()
+            .iter()
+            .map(|&e| edges[e].meta.clone())
+            .collect::<Vec<_>>();
+        if outputs.len() != carried.len()
+            || zip(&outputs, &meta).any(|(a, b)| a != b)
+            || layers[1..]
+                .iter()
+                .any(|layer| !same_structure(&body.graph, &layer.graph))
+        {
+            for layer in layers {
+                let inputs = carried.into_iter().chain(shared.iter().cloned());
+                carried = self.inline(layer, inputs)
+            }
+            return Ok(carried);
+        }
+
+        // 收集每次迭代绑定的外部张量
+        let mut layers = layers.into_iter();
+        let body = layers.next().unwrap();
+        let bindings = layers
+            .map(|mut layer| Bindings::take(&mut layer.graph))
+            .collect::<Vec<_>>();
+
+        let arg = {
+            let mut internal = self.0.borrow_mut();
+            let arg = Arg::dict([
+                ("body".into(), Arg::int(internal.bodies.len())),
+                ("times".into(), Arg::int(bindings.len() + 1)),
+            ]);
+            internal.bodies.push(Body {
+                namespace,
+                graph: body,
+                bindings: bindings.into(),
+            });
+            arg
+        };
+        let operator = Operator {
+            name: "repeat".into(),
+            arg: Some(arg),
+        };
+        Ok(self.push_op(node_name, operator, inputs, outputs))
+    }
+}
+
+impl<T> Context<T> {
+    pub(super) fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+
+    pub(super) fn get_meta(&self, i: usize) -> TensorMeta {
+        self.0.borrow().tensors[i].meta.clone()
+    }
+
+    fn push_op(
+        &self,
+        name: String,
+        operator: Operator,
+        inputs: Box<[usize]>,
+        meta: Vec<TensorMeta>,
+    ) -> Vec<Tensor<T>> {
+        let mut internal = self.0.borrow_mut();
+
         let start = internal.tensors.len();
         internal
             .tensors
@@ -174,27 +309,72 @@ impl<T> Context<T> {
 
         internal.operators.push(Op_ {
             name,
-            operator: Operator { name: op, arg },
+            operator,
             inputs,
             outputs: start..end,
         });
 
-        Ok((start..end)
+        (start..end)
             .map(|idx| Tensor {
                 idx,
                 ctx: Context(self.0.clone()),
             })
-            .collect())
+            .collect()
     }
-}
 
-impl<T> Context<T> {
-    pub(super) fn clone(&self) -> Self {
-        Self(self.0.clone())
-    }
-
-    pub(super) fn get_meta(&self, i: usize) -> TensorMeta {
-        self.0.borrow().tensors[i].meta.clone()
+    /// 将单独构造的图中的节点依次加入当前图，`inputs` 对应其全图输入，返回其全图输出。
+    fn inline(
+        &self,
+        graph: NNGraph<T>,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+    ) -> Vec<Tensor<T>> {
+        let ::graph::Graph { topo, nodes, edges } = graph.graph;
+        let mut edges = edges.into_iter().map(Some).collect::<Vec<_>>();
+        let mut tensor_map = vec![usize::MAX; edges.len()];
+        for (i, t) in inputs.into_iter().enumerate() {
+            tensor_map[i] = t.idx
+        }
+        for (topo, node) in zip(topo.iter(), nodes) {
+            let NodeRef { inputs, outputs } = topo;
+            let inputs = inputs
+                .iter()
+                .map(|&e| {
+                    if tensor_map[e] == usize::MAX {
+                        // 未映射，应该是权重
+                        let Edge { meta, external } = edges[e].take().unwrap();
+                        let External { name, item } = external.unwrap();
+                        let mut internal = self.0.borrow_mut();
+                        tensor_map[e] = internal.tensors.len();
+                        internal.tensors.push(Tensor_ {
+                            name,
+                            meta,
+                            external: Some(item),
+                        })
+                    }
+                    tensor_map[e]
+                })
+                .collect();
+            let meta = outputs
+                .clone()
+                .map(|e| edges[e].as_ref().unwrap().meta.clone())
+                .collect();
+            let Node { name, value } = node;
+            let tensors = self.push_op(name, value, inputs, meta);
+            // 保留输出上绑定的外部张量
+            let mut internal = self.0.borrow_mut();
+            for (e, t) in zip(outputs, tensors) {
+                let external = edges[e].take().unwrap().external;
+                internal.tensors[t.idx].external = external.map(|e| e.item);
+                tensor_map[e] = t.idx
+            }
+        }
+        topo.global_outputs()
+            .iter()
+            .map(|&e| Tensor {
+                idx: tensor_map[e],
+                ctx: self.clone(),
+            })
+            .collect()
     }
 
     fn into_graph(self, global_outputs: Vec<Tensor<T>>) -> NNGraph<T> {
@@ -202,6 +382,7 @@ impl<T> Context<T> {
             operators,
             tensors,
             n_inputs,
+            bodies,
             ..
         } = self.0.replace(Internal {
             namespace: Namespace::new("Ω"),
@@ -209,6 +390,8 @@ impl<T> Context<T> {
             operators: Default::default(),
             tensors: Default::default(),
             n_inputs: Default::default(),
+            fold_repeat: Default::default(),
+            bodies: Default::default(),
         });
 
         let global_outputs = global_outputs
@@ -285,17 +468,36 @@ impl<T> Context<T> {
         for (i, j) in global_outputs.into_iter().enumerate() {
             connections[i] = edge_map[j]
         }
-        NNGraph(::graph::Graph {
-            topo: unsafe {
-                GraphTopo::from_raw_parts(
-                    n_inputs,
-                    n_outputs,
-                    connections.into(),
-                    topo_nodes.into(),
-                )
+        NNGraph {
+            graph: ::graph::Graph {
+                topo: unsafe {
+                    GraphTopo::from_raw_parts(
+                        n_inputs,
+                        n_outputs,
+                        connections.into(),
+                        topo_nodes.into(),
+                    )
+                },
+                nodes: nodes.into(),
+                edges: edges.into(),
             },
-            nodes: nodes.into(),
-            edges: edges.into(),
-        })
+            bodies: bodies.into(),
+        }
     }
 }
+
+/// 两个计算图的拓扑、算子和张量元信息是否相同，外部张量绑定的对象可以不同。
+fn same_structure<T>(a: &::graph::Graph<Node, Edge<T>>, b: &::graph::Graph<Node, Edge<T>>) -> bool {
+    a.topo.n_inputs() == b.topo.n_inputs()
+        && a.topo.global_outputs() == b.topo.global_outputs()
+        && a.nodes.len() == b.nodes.len()
+        && a.edges.len() == b.edges.len()
+        && zip(a.topo.iter(), b.topo.iter()).all(|(a, b)| {
+            let NodeRef { inputs, outputs } = a;
+            inputs == b.inputs && outputs == b.outputs
+        })
+        && zip(&a.nodes, &b.nodes)
+            .all(|(a, b)| a.value.name == b.value.name && a.value.arg == b.value.arg)
+        && zip(&a.edges, &b.edges)
+            .all(|(a, b)| a.meta == b.meta && a.external.is_some() == b.external.is_some())
+}
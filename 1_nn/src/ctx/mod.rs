@@ -11,6 +11,7 @@ pub use tensor::{Tensor, TensorMeta};
 #[derive(Default)]
 pub struct GraphBuilder {
     op_lib: Rc<OpLib>,
+    fold_repeat: bool,
 }
 
 impl GraphBuilder {
@@ -28,6 +29,32 @@ impl GraphBuilder {
         );
         self
     }
+
+    /// 设置是否将 [`Context::repeat`] 构造为 `repeat` 节点，默认逐层展开。
+    pub fn fold_repeat(&mut self, fold: bool) -> &mut Self {
+        self.fold_repeat = fold;
+        self
+    }
+
+    /// 按注册的算子声明的原地计算关系合并存储块，返回合并的块数，见 [`mem::Graph::inplace`]。
+    pub fn inplace<T>(&self, graph: &mut mem::Graph<T>) -> usize {
+        graph.inplace(|node| match self.op_lib.get(&*node.value.name) {
+            Some(op) => op.inplace(node.value.arg.as_ref()),
+            None => Vec::new(),
+        })
+    }
+
+    /// 按注册的算子声明的布局要求插入 rearrange，返回插入的数量，见 [`mem::Graph::rearrange_inputs`]。
+    pub fn rearrange_inputs<T>(&self, graph: &mut mem::Graph<T>) -> usize {
+        graph.rearrange_inputs(|node| match self.op_lib.get(&*node.value.name) {
+            Some(op) => op.layout(node.value.arg.as_ref()),
+            None => Vec::new(),
+        })
+    }
+
+    pub(crate) fn op_lib(&self) -> Rc<OpLib> {
+        self.op_lib.clone()
+    }
 }
 
 #[repr(transparent)]
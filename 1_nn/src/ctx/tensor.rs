@@ -108,7 +108,7 @@ impl<T> Tensor<T> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct TensorMeta {
     pub dt: DigitLayout,
     pub shape: Box<[Dim]>,
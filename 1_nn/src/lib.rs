@@ -1,21 +1,32 @@
+mod analyze;
+mod budget;
 mod ctx;
 mod nn;
+mod repeat;
+mod validate;
 
 use std::collections::HashMap;
 
 pub mod op;
+pub mod pass;
 
 pub use arg::{Arg, Dim};
-pub use graph::{Graph, GraphTopo, Named, NodeRef, TopoNode};
-pub use mem::{BlobLifeTime, Exec, External, Info, Node, Operator as OpInfo};
+pub use graph::{Graph, GraphTopo, Named, NodeRef, TopoError, TopoNode};
+pub use mem::{BlobLifeTime, Exec, External, Info, LayoutReq, Node, Operator as OpInfo, Pool};
 pub use tensor::{Tensor, digit_layout, ndarray_layout};
 
+pub use analyze::DynamicRegion;
 pub use ctx::*;
 pub use nn::*;
+pub use repeat::{Bindings, Body, LoweredBody};
+pub use validate::GraphError;
 
 #[derive(Clone)]
-#[repr(transparent)]
-pub struct NNGraph<T>(pub graph::Graph<Node, Edge<T>>);
+pub struct NNGraph<T> {
+    pub graph: graph::Graph<Node, Edge<T>>,
+    /// `repeat` 节点引用的循环体。
+    pub bodies: Box<[Body<T>]>,
+}
 
 #[derive(Clone)]
 pub struct Edge<T> {
@@ -25,16 +36,23 @@ pub struct Edge<T> {
 
 impl<T> NNGraph<T> {
     /// 从逻辑连接图下降到存储管理图
+    ///
+    /// `repeat` 节点保持为一个节点，循环体需要先取出再分别下降，见 [`Body::lower`]；
+    /// 需要展开循环时先调用 [`NNGraph::unroll`]。
     pub fn lower<U>(
         self,
         value: &HashMap<&str, usize>,
         mut map: impl FnMut(T) -> Tensor<U, 2>,
     ) -> mem::Graph<U> {
-        let Self(graph::Graph {
-            topo,
-            mut nodes,
-            edges,
-        }) = self;
+        let Self {
+            graph:
+                graph::Graph {
+                    topo,
+                    mut nodes,
+                    edges,
+                },
+            ..
+        } = self;
         for node in &mut nodes {
             if let Some(arg) = &mut node.value.arg {
                 *arg = std::mem::replace(arg, Arg::Bool(false)).substitute(value)
@@ -54,7 +72,8 @@ impl<T> NNGraph<T> {
                     assert_eq!(tensor.shape(), shape, "shape mismatch: {name}");
                     tensor.map(|item| mem::Info::External(External { name, item }))
                 }
-                None => Tensor::from_dim_slice(meta.dt, &shape).map(mem::Info::Internal),
+                None => Tensor::from_dim_slice(meta.dt, &shape)
+                    .map(|size| mem::Info::Internal(size, Pool::DEFAULT)),
             }
         });
         mem::Graph::new(topo, nodes, edges)
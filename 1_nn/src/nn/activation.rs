@@ -5,6 +5,10 @@ pub enum Activation {
     SwiGLU,
     SiLU,
     GeLU,
+    Tanh,
+    Sigmoid,
+    /// `relu(x)^2`。
+    ReluSq,
 }
 
 impl<T> NuralNetwork<T> for Activation {
@@ -27,6 +31,9 @@ impl<T> NuralNetwork<T> for Activation {
                 // format
                 ctx.call("", "gelu", None, [x])
             }
+            Self::Tanh => ctx.call("", "tanh", None, [x]),
+            Self::Sigmoid => ctx.call("", "sigmoid", None, [x]),
+            Self::ReluSq => ctx.call("", "relu-sq", None, [x]),
         };
 
         Ok((ctx, outputs?))
@@ -4,7 +4,8 @@
 };
 use crate::{
     TPAction,
-    weight_types::{AttnQKV, RowTPWeight},
+    op::attention::{Attention as AttentionOp, Mask},
+    weight_types::{AttnQKV, ColumnTPWeight, FfnGateUp, RowTPWeight},
 };
 use tensor::digit_layout::types;
 
@@ -17,6 +18,20 @@ pub struct Attention<T> {
     pub k_norm: Option<Normalization<T>>,
     pub rope: Option<RoPE<T>>,
     pub output: Linear<T>,
+    /// 使用因果掩码，解码器中为 `true`，编码器中为 `false`。
+    pub causal: bool,
+}
+
+/// 交叉注意力，q 来自 `x`，k、v 来自编码器的输出 `memory`，两者的长度可以不同。
+///
+/// 输入为 `[x, memory, residual]`，`kv` 依次输出 k 和 v。
+#[derive(Clone)]
+pub struct CrossAttention<T> {
+    pub nh: usize,
+    pub nkvh: usize,
+    pub q: Linear<T>,
+    pub kv: Linear<T>,
+    pub output: Linear<T>,
 }
 
 #[derive(Clone)]
@@ -37,6 +52,7 @@ impl<T> Attention<T> {
             k_norm,
             rope,
             output,
+            causal,
         } = self;
         assert_eq!(nh % dist.total, 0);
         assert_eq!(nkvh % dist.total, 0);
@@ -60,6 +76,7 @@ impl<T> Attention<T> {
                 },
             ),
             output: output.parallel(TPAction::new(RowTPWeight, dist)),
+            causal,
         }
     }
 }
@@ -80,6 +97,7 @@ impl<T> NuralNetwork<T> for Attention<T> {
             k_norm,
             rope,
             output,
+            causal,
         } = self;
         destruct!([x] = ctx.trap("attn-qkv", qkv, [x])?);
         dims!([_, dqkv] = x);
@@ -136,7 +154,65 @@ impl<T> NuralNetwork<T> for Attention<T> {
             None => [q, k],
         };
 
-        destruct!([o] = ctx.call("", "attention", Some(dh.into()), [q, k, v,])?);
+        let mask = if causal {
+            Mask::Causal
+        } else {
+            Mask::Bidirectional
+        };
+        let arg = AttentionOp::arg(dh, mask);
+        destruct!([o] = ctx.call("", "attention", Some(arg), [q, k, v])?);
+
+        let outputs = ctx.trap("attn-output", output, [o, residual]);
+
+        Ok((ctx, outputs?))
+    }
+}
+
+impl<T> CrossAttention<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> CrossAttention<TPTensor<T>> {
+        let Self {
+            nh,
+            nkvh,
+            q,
+            kv,
+            output,
+        } = self;
+        assert_eq!(nh % dist.total, 0);
+        assert_eq!(nkvh % dist.total, 0);
+        CrossAttention {
+            nh: nh / dist.total * dist.len,
+            nkvh: nkvh / dist.total * dist.len,
+            q: q.parallel(TPAction::new(ColumnTPWeight, dist)),
+            kv: kv.parallel(TPAction::new(FfnGateUp, dist)),
+            output: output.parallel(TPAction::new(RowTPWeight, dist)),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for CrossAttention<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        destruct!([x, memory, residual] = inputs);
+
+        let Self {
+            nh,
+            nkvh,
+            q,
+            kv,
+            output,
+        } = self;
+        destruct!([q] = ctx.trap("attn-q", q, [x])?);
+        dims!([_, dq] = q);
+        let dh = dq.clone() / nh;
+
+        destruct!([kv] = ctx.trap("attn-kv", kv, [memory])?);
+        destruct!([k, v] = kv.split("split-kv", 1, [nkvh.into(), nkvh.into()])?);
+
+        let arg = AttentionOp::arg(dh, Mask::None);
+        destruct!([o] = ctx.call("", "attention", Some(arg), [q, k, v])?);
 
         let outputs = ctx.trap("attn-output", output, [o, residual]);
 
@@ -0,0 +1,136 @@
+use super::{
+    Activation, Context, NNError, Normalization, NuralNetwork, Table, Tensor, TransformerBlk,
+    inputs::SplitInputs, macros::destruct,
+};
+use arg::{Arg, Dim};
+use tensor::digit_layout::DigitLayout;
+
+/// Whisper 风格的音频编码器，卷积前端降采样后加上位置编码，依次经过每个块和输出归一化。
+///
+/// 输入为 `[mel [n_mel, n_frame], pos [n_ctx]]`，`n_ctx` 是卷积前端输出的长度，输出为 `[memory [n_ctx, d]]`。
+#[derive(Clone)]
+pub struct AudioEncoder<T> {
+    pub stem: ConvStem<T>,
+    /// 形状为 `[n_ctx_max, d]` 的位置编码表，与 [`Embedding::wpe`](super::Embedding::wpe) 相同，
+    /// 可以是学习的或预先计算的正弦编码。
+    pub wpe: Table<T>,
+    pub blks: Box<[TransformerBlk<T>]>,
+    pub out_norm: Normalization<T>,
+}
+
+/// 两层一维卷积的前端，每层卷积后经过激活，输入为 `[c, l]`，输出为 `[l', d]`。
+#[derive(Clone)]
+pub struct ConvStem<T> {
+    pub conv1: Conv1d<T>,
+    pub conv2: Conv1d<T>,
+    pub act: Activation,
+}
+
+/// 一维卷积，权重形状为 `[m, c, k]`，输入为 `[c, l]`，输出为 `[m, l']`，
+/// 作为批大小为 1 的 `conv` 计算。
+#[derive(Clone)]
+pub struct Conv1d<T> {
+    pub dt: DigitLayout,
+    pub shape: [usize; 3],
+    pub stride: usize,
+    pub padding: usize,
+    pub weight: T,
+    pub bias: Option<(DigitLayout, T)>,
+}
+
+impl<T> NuralNetwork<T> for AudioEncoder<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            stem,
+            wpe,
+            blks,
+            out_norm,
+        } = self;
+
+        let SplitInputs { x: mel, shared, .. } = SplitInputs::new(&ctx, inputs, 1, false)?;
+        destruct!([pos] = shared.clone());
+
+        destruct!([x] = ctx.trap("conv-stem", stem, [mel])?);
+
+        let Table { row, weight } = wpe;
+        let d = x.shape()[1].clone();
+        let wpe = ctx.load_external("wpe", x.dt(), [row.into(), d], weight);
+        destruct!([pos] = ctx.call("pos-embd", "embedding", None, [wpe, pos])?);
+        destruct!([x] = ctx.call("", "add", None, [x, pos])?);
+
+        destruct!(
+            [x] = ctx.repeat(
+                "blk",
+                blks.into_iter().map(TransformerBlk::bidirectional),
+                [x],
+                shared
+            )?
+        );
+        let outputs = ctx.trap("out-norm", out_norm, [x])?;
+
+        Ok((ctx, outputs))
+    }
+}
+
+impl<T> NuralNetwork<T> for ConvStem<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self { conv1, conv2, act } = self;
+
+        destruct!([x] = inputs);
+        destruct!([x] = ctx.trap("conv1", conv1, [x])?);
+        destruct!([x] = ctx.trap("act1", act, [x])?);
+        destruct!([x] = ctx.trap("conv2", conv2, [x])?);
+        destruct!([x] = ctx.trap("act2", act, [x])?);
+
+        // [d, l] -> [l, d]
+        let x = x.transpose("", vec![1, 0])?;
+
+        Ok((ctx, vec![x]))
+    }
+}
+
+impl<T> NuralNetwork<T> for Conv1d<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            dt,
+            shape,
+            stride,
+            padding,
+            weight,
+            bias,
+        } = self;
+        let [m, _, _] = shape;
+
+        destruct!([x] = inputs);
+        // [c, l] -> [1, c, l]
+        let c = x.shape()[0].clone();
+        let x = x.tile("", 0, [1.into(), c])?;
+        let w = ctx.load_external("weight", dt, shape.map(Dim::from), weight);
+        let mut inputs = vec![x, w];
+        if let Some((dt, bias)) = bias {
+            inputs.push(ctx.load_external("bias", dt, [m.into()], bias))
+        }
+
+        let arg = Arg::dict([
+            ("stride".into(), Arg::arr([Arg::int(stride)])),
+            ("padding".into(), Arg::arr([Arg::int(padding)])),
+        ]);
+        destruct!([x] = ctx.call("", "conv", Some(arg), inputs)?);
+        // [1, m, l'] -> [m, l']
+        let x = x.merge("", 0, 2)?;
+
+        Ok((ctx, vec![x]))
+    }
+}
@@ -54,7 +54,9 @@ impl<T> NuralNetwork<T> for CogVLM<T> {
             .into_iter()
             .enumerate()
             .try_fold(x, |x, (i, blk)| {
-                destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x, pos.clone()])?);
+                destruct!(
+                    [x] = ctx.trap(format!("blk{i}"), blk.bidirectional(), [x, pos.clone()])?
+                );
                 Ok(x)
             })?;
 
@@ -0,0 +1,103 @@
+use super::{
+    Context, Embedding, NNError, NuralNetwork, Tensor,
+    inputs::SplitInputs,
+    macros::destruct,
+    output_head::{OutputHead, output},
+};
+use crate::op::OpError;
+
+/// 仅解码器模型的骨架，词嵌入后依次经过每个块，有输出头时再取出 `out_idx` 指定的 token 计算 logits。
+///
+/// 输入为 `[tokens, ..块声明的输入, out_idx]`，没有输出头时没有 `out_idx`。
+/// 词嵌入有位置编码表时，块需要声明 [`BlockInput::Pos`]。
+#[derive(Clone)]
+pub struct Decoder<T, Blk> {
+    pub embedding: Embedding<T>,
+    pub blks: Box<[Blk]>,
+    pub output_head: Option<OutputHead<T>>,
+}
+
+/// 解码器块在隐藏状态 `x` 之后需要的输入。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockInput {
+    /// 形状为 `[n_tok]` 的位置。
+    Pos,
+    /// 编码器的输出，长度与 `n_tok` 无关。
+    Memory,
+}
+
+/// 解码器块，输入为 `[x, ..INPUTS]`，输出为 `[x]`。
+///
+/// 循环状态由块自己声明为外部张量，不占用输入。
+/// 块可以在 `x` 之后多输出一些张量传给下一个块，接在下一个块输入的最后，第一个块没有这些输入，例如 RWKV-7 的 `v_first`。
+pub trait DecoderBlock<T>: NuralNetwork<T> {
+    /// 块在 `x` 之后依次需要的输入。
+    const INPUTS: &'static [BlockInput];
+    /// 每层结构相同，可以折叠为循环。
+    const REPEAT: bool = false;
+}
+
+impl<T, Blk: DecoderBlock<T>> Decoder<T, Blk> {
+    /// 块在 `x` 之后需要的输入，即模型在 `tokens` 之后、`out_idx` 之前的输入。
+    pub const fn block_inputs(&self) -> &'static [BlockInput] {
+        Blk::INPUTS
+    }
+}
+
+impl<T, Blk: DecoderBlock<T>> NuralNetwork<T> for Decoder<T, Blk> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            embedding,
+            blks,
+            output_head,
+        } = self;
+
+        let SplitInputs {
+            x: tokens,
+            shared,
+            out_idx,
+        } = SplitInputs::new(&ctx, inputs, Blk::INPUTS.len(), output_head.is_some())?;
+
+        // 有位置编码表时词嵌入也需要位置
+        let mut embd_inputs = vec![tokens];
+        if embedding.wpe.is_some() {
+            match Blk::INPUTS
+                .iter()
+                .position(|input| *input == BlockInput::Pos)
+            {
+                Some(i) => embd_inputs.push(shared[i].clone()),
+                None => {
+                    return Err(NNError {
+                        name: ctx.path(),
+                        err: OpError::ArityError,
+                    });
+                }
+            }
+        }
+        destruct!([x] = ctx.trap("embedding", embedding, embd_inputs)?);
+
+        let x = if Blk::REPEAT {
+            destruct!([x] = ctx.repeat("blk", blks, [x], shared)?);
+            x
+        } else {
+            let (x, _) = blks.into_iter().enumerate().try_fold(
+                (x, Vec::new()),
+                |(x, carried), (i, blk)| {
+                    let inputs = [x].into_iter().chain(shared.iter().cloned()).chain(carried);
+                    let mut outputs = ctx.trap(format!("blk{i}"), blk, inputs)?.into_iter();
+                    let x = outputs.next().unwrap();
+                    Ok((x, outputs.collect()))
+                },
+            )?;
+            x
+        };
+
+        let x = output(&mut ctx, output_head, x, out_idx)?;
+
+        Ok((ctx, vec![x]))
+    }
+}
@@ -1,4 +1,4 @@
-﻿use super::{Context, NNError, NuralNetwork, TPTensor, Tensor};
+﻿use super::{Context, NNError, Normalization, NuralNetwork, TPTensor, Tensor, macros::destruct};
 use tensor::digit_layout::DigitLayout;
 
 #[derive(Clone)]
@@ -7,6 +7,8 @@ pub struct Embedding<T> {
     pub d: usize,
     pub wte: Table<T>,
     pub wpe: Option<Table<T>>,
+    /// 词嵌入之后的归一化，例如 RWKV 的 ln0。
+    pub norm: Option<Normalization<T>>,
 }
 
 #[derive(Clone)]
@@ -17,7 +19,13 @@ pub struct Table<T> {
 
 impl<T> Embedding<T> {
     pub fn tensor_parallel(self) -> Embedding<TPTensor<T>> {
-        let Self { dt, d, wte, wpe } = self;
+        let Self {
+            dt,
+            d,
+            wte,
+            wpe,
+            norm,
+        } = self;
         Embedding {
             dt,
             d,
@@ -29,6 +37,7 @@ impl<T> Embedding<T> {
                 row,
                 weight: weight.into(),
             }),
+            norm: norm.map(Normalization::tensor_parallel),
         }
     }
 }
@@ -39,7 +48,13 @@ impl<T> NuralNetwork<T> for Embedding<T> {
         inputs: impl IntoIterator<Item = Tensor<T>>,
         mut ctx: Context<T>,
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
-        let Self { dt, d, wte, wpe } = self;
+        let Self {
+            dt,
+            d,
+            wte,
+            wpe,
+            norm,
+        } = self;
         let mut inputs = inputs.into_iter();
 
         let Table { row, weight } = wte;
@@ -57,8 +72,16 @@ impl<T> NuralNetwork<T> for Embedding<T> {
                 // format
                 ctx.call("", "embedding", None, [wte, tokens])
             }
+        }?;
+
+        let outputs = match norm {
+            Some(norm) => {
+                destruct!([x] = outputs);
+                ctx.trap("norm", norm, [x])?
+            }
+            None => outputs,
         };
 
-        Ok((ctx, outputs?))
+        Ok((ctx, outputs))
     }
 }
@@ -0,0 +1,170 @@
+use super::{
+    Attention, BlockInput, Context, CrossAttention, Decoder, DecoderBlock, Distribution, Embedding,
+    Mlp, NNError, Normalization, NuralNetwork, TPTensor, Tensor, TransformerBlk,
+    inputs::SplitInputs, macros::destruct,
+};
+
+/// 编码器-解码器，编码器的输出作为解码器每个块交叉注意力的 k、v。
+///
+/// 输入为 `[..编码器的输入, tokens, pos, out_idx]`，没有输出头时没有 `out_idx`。
+#[derive(Clone)]
+pub struct EncoderDecoder<T, Enc = Encoder<T>> {
+    pub encoder: Enc,
+    pub decoder: Decoder<T, CrossTransformerBlk<T>>,
+}
+
+/// 编码器，词嵌入后依次经过每个块和输出归一化，输入为 `[tokens, pos]`，输出为 `[memory]`。
+#[derive(Clone)]
+pub struct Encoder<T> {
+    pub embedding: Embedding<T>,
+    pub blks: Box<[TransformerBlk<T>]>,
+    pub out_norm: Normalization<T>,
+}
+
+/// 带交叉注意力的 Transformer 块，输入为 `[x, pos, memory]`。
+#[derive(Clone)]
+pub struct CrossTransformerBlk<T> {
+    pub attn_norm: Normalization<T>,
+    pub attn: Attention<T>,
+    pub cross_attn_norm: Normalization<T>,
+    pub cross_attn: CrossAttention<T>,
+    pub ffn_norm: Normalization<T>,
+    pub ffn: Mlp<T>,
+    pub all_reduce: bool,
+}
+
+impl<T> CrossTransformerBlk<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> CrossTransformerBlk<TPTensor<T>> {
+        let Self {
+            attn_norm,
+            attn,
+            cross_attn_norm,
+            cross_attn,
+            ffn_norm,
+            ffn,
+            ..
+        } = self;
+        CrossTransformerBlk {
+            attn_norm: attn_norm.tensor_parallel(),
+            attn: attn.tensor_parallel(dist),
+            cross_attn_norm: cross_attn_norm.tensor_parallel(),
+            cross_attn: cross_attn.tensor_parallel(dist),
+            ffn_norm: ffn_norm.tensor_parallel(),
+            ffn: ffn.tensor_parallel(dist),
+            all_reduce: !dist.is_mono(),
+        }
+    }
+}
+
+impl<T, Enc: NuralNetwork<T>> NuralNetwork<T> for EncoderDecoder<T, Enc> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self { encoder, decoder } = self;
+
+        // 解码器的输入在最后，其余都是编码器的输入
+        let head = decoder.output_head.is_some();
+        let mut inputs = inputs.into_iter().collect::<Vec<_>>();
+        let n_encoder = inputs.len().saturating_sub(2 + head as usize);
+        let decoder_inputs = inputs.split_off(n_encoder);
+        let SplitInputs {
+            x: tokens,
+            shared,
+            out_idx,
+        } = SplitInputs::new(&ctx, decoder_inputs, 1, head)?;
+
+        destruct!([memory] = ctx.trap("encoder", encoder, inputs)?);
+
+        let inputs = [tokens]
+            .into_iter()
+            .chain(shared)
+            .chain([memory])
+            .chain(out_idx);
+        let outputs = ctx.trap("decoder", decoder, inputs)?;
+
+        Ok((ctx, outputs))
+    }
+}
+
+impl<T> NuralNetwork<T> for Encoder<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            embedding,
+            blks,
+            out_norm,
+        } = self;
+
+        let SplitInputs {
+            x: tokens, shared, ..
+        } = SplitInputs::new(&ctx, inputs, 1, false)?;
+
+        destruct!([x] = ctx.trap("embedding", embedding, [tokens])?);
+        destruct!(
+            [x] = ctx.repeat(
+                "blk",
+                blks.into_iter().map(TransformerBlk::bidirectional),
+                [x],
+                shared
+            )?
+        );
+        let outputs = ctx.trap("out-norm", out_norm, [x])?;
+
+        Ok((ctx, outputs))
+    }
+}
+
+impl<T> DecoderBlock<T> for CrossTransformerBlk<T> {
+    const INPUTS: &'static [BlockInput] = &[BlockInput::Pos, BlockInput::Memory];
+    const REPEAT: bool = true;
+}
+
+impl<T> NuralNetwork<T> for CrossTransformerBlk<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            attn_norm,
+            attn,
+            cross_attn_norm,
+            cross_attn,
+            ffn_norm,
+            ffn,
+            all_reduce,
+        } = self;
+
+        destruct!([x, pos, memory] = inputs);
+
+        let all_reduce = |ctx: &mut Context<T>, tensors: Vec<Tensor<T>>| {
+            if all_reduce {
+                ctx.call("", "all-reduce", Some("sum".into()), tensors)
+            } else {
+                Ok(tensors)
+            }
+        };
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("attn-norm", attn_norm, [x])?);
+        let tensors = ctx.trap("attn", attn, [x, pos, residual])?;
+        destruct!([x] = all_reduce(&mut ctx, tensors)?);
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("cross-attn-norm", cross_attn_norm, [x])?);
+        let tensors = ctx.trap("cross-attn", cross_attn, [x, memory, residual])?;
+        destruct!([x] = all_reduce(&mut ctx, tensors)?);
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("ffn-norm", ffn_norm, [x])?);
+        let tensors = ctx.trap("ffn", ffn, [x, residual])?;
+        let tensors = all_reduce(&mut ctx, tensors)?;
+
+        Ok((ctx, tensors))
+    }
+}
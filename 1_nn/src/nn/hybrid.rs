@@ -0,0 +1,174 @@
+use super::{
+    Attention, BlockInput, Context, Decoder, DecoderBlock, Embedding, Mamba2Block, MambaBlock, Mlp,
+    Moe, NNError, Normalization, NuralNetwork, OutputHead, Tensor, macros::destruct,
+    transformer_blk::PreNorm,
+};
+
+/// 混合解码器，每层由注意力或 Mamba 混合层和可选的 MLP 或 MoE 前馈网络组成，例如 Jamba。
+pub type Hybrid<T> = Decoder<T, Layer<T>>;
+
+/// 混合层的类型。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MixerType {
+    Attention,
+    Mamba,
+    Mamba2,
+}
+
+/// 前馈网络的类型。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FfnType {
+    Mlp,
+    Moe,
+}
+
+/// 混合解码器中一层的类型，没有前馈网络时 `ffn` 为 `None`。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LayerType {
+    pub mixer: MixerType,
+    pub ffn: Option<FfnType>,
+}
+
+/// 混合层，输入为 `[x, pos]`，输出为 `[x]`，Mamba 块不使用 `pos`。
+///
+/// 混合层自带归一化和残差。
+#[derive(Clone)]
+pub enum Mixer<T> {
+    Attention {
+        norm: Normalization<T>,
+        attn: Attention<T>,
+    },
+    Mamba(MambaBlock<T>),
+    Mamba2(Mamba2Block<T>),
+}
+
+/// 前馈网络，输入为 `[x, residual]`，输出为 `[x]`。
+#[derive(Clone)]
+pub enum Ffn<T> {
+    Mlp(Mlp<T>),
+    Moe(Moe<T>),
+}
+
+/// 混合解码器的一层，输入为 `[x, pos]`，输出为 `[x]`。
+///
+/// `ffn` 为前馈网络和它之前的归一化，纯 Mamba 层没有前馈网络。
+/// 张量并行时 `all_reduce` 为真，注意力和前馈网络的输出需要 all-reduce。
+#[derive(Clone)]
+pub struct Layer<T> {
+    pub mixer: Mixer<T>,
+    pub ffn: Option<(Normalization<T>, Ffn<T>)>,
+    pub all_reduce: bool,
+}
+
+impl<T> Layer<T> {
+    pub const fn ty(&self) -> LayerType {
+        LayerType {
+            mixer: match self.mixer {
+                Mixer::Attention { .. } => MixerType::Attention,
+                Mixer::Mamba(_) => MixerType::Mamba,
+                Mixer::Mamba2(_) => MixerType::Mamba2,
+            },
+            ffn: match self.ffn {
+                Some((_, Ffn::Mlp(_))) => Some(FfnType::Mlp),
+                Some((_, Ffn::Moe(_))) => Some(FfnType::Moe),
+                None => None,
+            },
+        }
+    }
+}
+
+impl<T> Hybrid<T> {
+    /// 按每层的类型依次构造各层，`layer` 构造的层必须与给定的类型一致。
+    pub fn new(
+        embedding: Embedding<T>,
+        types: impl IntoIterator<Item = LayerType>,
+        mut layer: impl FnMut(usize, LayerType) -> Layer<T>,
+        output_head: Option<OutputHead<T>>,
+    ) -> Self {
+        let blks = types
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let blk = layer(i, ty);
+                assert_eq!(blk.ty(), ty, "layer {i} type mismatch");
+                blk
+            })
+            .collect();
+        Self {
+            embedding,
+            blks,
+            output_head,
+        }
+    }
+
+    /// 每层的类型。
+    pub fn types(&self) -> impl Iterator<Item = LayerType> + '_ {
+        self.blks.iter().map(Layer::ty)
+    }
+}
+
+impl<T> DecoderBlock<T> for Layer<T> {
+    const INPUTS: &'static [BlockInput] = &[BlockInput::Pos];
+}
+
+impl<T> NuralNetwork<T> for Layer<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            mixer,
+            ffn,
+            all_reduce,
+        } = self;
+
+        destruct!([x, pos] = inputs);
+        let x = match mixer {
+            Mixer::Attention { norm, attn } => {
+                let attn = PreNorm {
+                    names: ["attn-norm", "attn"],
+                    norm,
+                    body: attn,
+                    all_reduce,
+                };
+                let tensors;
+                (ctx, tensors) = attn.launch([x, pos], ctx)?;
+                destruct!([x] = tensors);
+                x
+            }
+            Mixer::Mamba(blk) => {
+                destruct!([x] = ctx.trap("mamba", blk, [x])?);
+                x
+            }
+            Mixer::Mamba2(blk) => {
+                destruct!([x] = ctx.trap("mamba", blk, [x])?);
+                x
+            }
+        };
+
+        match ffn {
+            Some((norm, ffn)) => PreNorm {
+                names: ["ffn-norm", "ffn"],
+                norm,
+                body: ffn,
+                all_reduce,
+            }
+            .launch([x], ctx),
+            None => Ok((ctx, vec![x])),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for Ffn<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        match self {
+            Self::Mlp(mlp) => mlp.launch(inputs, ctx),
+            Self::Moe(moe) => moe.launch(inputs, ctx),
+        }
+    }
+}
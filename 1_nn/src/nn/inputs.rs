@@ -0,0 +1,37 @@
+use super::{Context, NNError, Tensor};
+use crate::op::OpError;
+
+/// 按 `[x, ..shared, out_idx]` 拆分的模型输入。
+///
+/// `x` 是主输入，例如解码器的 tokens 或音频编码器的 mel 谱，`shared` 是每层共享的输入，
+/// 不需要输出头时没有 `out_idx`。
+pub(super) struct SplitInputs<T> {
+    pub x: Tensor<T>,
+    pub shared: Vec<Tensor<T>>,
+    pub out_idx: Option<Tensor<T>>,
+}
+
+impl<T> SplitInputs<T> {
+    /// 按 `n_shared` 个共享输入和是否有 `out_idx` 拆分输入，数量不符时报错。
+    pub fn new(
+        ctx: &Context<T>,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        n_shared: usize,
+        out_idx: bool,
+    ) -> Result<Self, NNError> {
+        let mut inputs = inputs.into_iter().collect::<Vec<_>>();
+        if inputs.len() != 1 + n_shared + out_idx as usize {
+            return Err(NNError {
+                name: ctx.path(),
+                err: OpError::ArityError,
+            });
+        }
+        let out_idx = if out_idx { inputs.pop() } else { None };
+        let x = inputs.remove(0);
+        Ok(Self {
+            x,
+            shared: inputs,
+            out_idx,
+        })
+    }
+}
@@ -1,14 +1,7 @@
-﻿use super::{
-    Context, Distribution, Embedding, NNError, NuralNetwork, TPTensor, Tensor, TransformerBlk,
-    macros::destruct, output_head::OutputHead,
-};
+﻿use super::{Decoder, Distribution, OutputHead, TPTensor, TransformerBlk};
 
-#[derive(Clone)]
-pub struct LLaMA<T> {
-    pub embedding: Embedding<T>,
-    pub blks: Box<[TransformerBlk<T>]>,
-    pub output_head: Option<OutputHead<T>>,
-}
+/// LLaMA，每层都是 [`TransformerBlk`] 的解码器。
+pub type LLaMA<T> = Decoder<T, TransformerBlk<T>>;
 
 impl<T> LLaMA<T> {
     pub fn tensor_parallel(self, dist: Distribution) -> LLaMA<TPTensor<T>> {
@@ -27,40 +20,3 @@ impl<T> LLaMA<T> {
         }
     }
 }
-
-impl<T> NuralNetwork<T> for LLaMA<T> {
-    fn launch(
-        self,
-        inputs: impl IntoIterator<Item = Tensor<T>>,
-        mut ctx: Context<T>,
-    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
-        let Self {
-            embedding,
-            blks,
-            output_head,
-        } = self;
-
-        let mut inputs = inputs.into_iter();
-        let tokens = inputs.next().unwrap();
-        let pos = inputs.next().unwrap();
-
-        destruct!([x] = ctx.trap("embedding", embedding, [tokens])?);
-
-        let x = blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
-            destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x, pos.clone()])?);
-            Ok(x)
-        })?;
-
-        let x = if let Some(OutputHead { out_norm, lm_head }) = output_head {
-            let out_idx = inputs.next().unwrap();
-            destruct!([x] = ctx.call("out-gather", "embedding", None, [x, out_idx])?);
-            destruct!([x] = ctx.trap("out-norm", out_norm, [x])?);
-            destruct!([x] = ctx.trap("lm-head", lm_head, [x])?);
-            x
-        } else {
-            x
-        };
-
-        Ok((ctx, vec![x]))
-    }
-}
@@ -0,0 +1,29 @@
+use super::{Activation, Context, Linear, NNError, NuralNetwork, Tensor, macros::destruct};
+
+/// 低秩投影 `up(act(down(x)))`，`up` 的偏置作为投影结果的基准值。
+#[derive(Clone)]
+pub struct Lora<T> {
+    pub down: Linear<T>,
+    pub act: Option<Activation>,
+    pub up: Linear<T>,
+}
+
+impl<T> NuralNetwork<T> for Lora<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self { down, act, up } = self;
+
+        destruct!([x] = inputs);
+        destruct!([x] = ctx.trap("down", down, [x])?);
+        let x = match act {
+            Some(act) => ctx.trap("act", act, [x])?.remove(0),
+            None => x,
+        };
+        destruct!([x] = ctx.trap("up", up, [x])?);
+
+        Ok((ctx, vec![x]))
+    }
+}
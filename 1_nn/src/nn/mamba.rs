@@ -1,18 +1,14 @@
 use super::{
-    Context, Distribution, Embedding, NNError, Normalization, NuralNetwork, TPTensor, Tensor,
-    output_head::OutputHead,
+    BlockInput, Context, Decoder, DecoderBlock, Distribution, NNError, Normalization, NuralNetwork,
+    OutputHead, RecurrentState, TPTensor, Tensor, state::call_with_state,
 };
 use crate::macros::{destruct, dims};
 use crate::{Activation, Linear, TPAction, weight_types::RowTPWeight};
 use arg::{Arg, Dim};
 use tensor::digit_layout::DigitLayout;
 
-#[derive(Clone)]
-pub struct Mamba<T> {
-    pub embedding: Embedding<T>,
-    pub blks: Box<[MambaBlock<T>]>,
-    pub output_head: Option<OutputHead<T>>,
-}
+/// Mamba，每层都是 [`MambaBlock`] 的解码器。
+pub type Mamba<T> = Decoder<T, MambaBlock<T>>;
 
 impl<T> Mamba<T> {
     pub fn tensor_parallel(self, dist: Distribution) -> Mamba<TPTensor<T>> {
@@ -32,43 +28,6 @@ impl<T> Mamba<T> {
     }
 }
 
-impl<T> NuralNetwork<T> for Mamba<T> {
-    fn launch(
-        self,
-        inputs: impl IntoIterator<Item = Tensor<T>>,
-        mut ctx: Context<T>,
-    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
-        let Self {
-            embedding,
-            blks,
-            output_head,
-        } = self;
-
-        let mut inputs = inputs.into_iter();
-        let tokens = inputs.next().unwrap();
-        let pos = inputs.next().unwrap();
-
-        destruct!([x] = ctx.trap("embedding", embedding, [tokens])?);
-
-        let x = blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
-            destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x, pos.clone()])?);
-            Ok(x)
-        })?;
-
-        let x = if let Some(OutputHead { out_norm, lm_head }) = output_head {
-            let out_idx = inputs.next().unwrap();
-            destruct!([x] = ctx.call("out-gather", "embedding", None, [x, out_idx])?);
-            destruct!([x] = ctx.trap("out-norm", out_norm, [x])?);
-            destruct!([x] = ctx.trap("lm-head", lm_head, [x])?);
-            x
-        } else {
-            x
-        };
-
-        Ok((ctx, vec![x]))
-    }
-}
-
 #[derive(Clone)]
 pub struct MambaBlock<T> {
     pub mamba_norm: Normalization<T>,
@@ -118,6 +77,10 @@ impl<T> MambaBlock<T> {
     }
 }
 
+impl<T> DecoderBlock<T> for MambaBlock<T> {
+    const INPUTS: &'static [BlockInput] = &[];
+}
+
 impl<T> NuralNetwork<T> for MambaBlock<T> {
     fn launch(
         self,
@@ -136,7 +99,7 @@ impl<T> NuralNetwork<T> for MambaBlock<T> {
                     out_proj,
                 },
         } = self;
-        destruct!([x, _pos] = inputs);
+        destruct!([x] = inputs);
         dims!([_l, _d] = x);
 
         let residual = x.clone();
@@ -162,6 +125,8 @@ pub struct CausalConv1d<T> {
     pub casual_conv1d_b: T,
     pub d_kernel: usize,
     pub d_inner: usize,
+    /// 形状为 `[d_kernel - 1, d_inner]` 的状态，即最近的输入。
+    pub state: Option<RecurrentState<T>>,
     groups: usize,
     padding: usize,
 }
@@ -181,6 +146,7 @@ impl<T> CausalConv1d<T> {
             casual_conv1d_b,
             d_kernel,
             d_inner,
+            state: None,
             groups: d_inner,
             padding: d_kernel - 1,
         }
@@ -192,6 +158,7 @@ impl<T> CausalConv1d<T> {
             casual_conv1d_b,
             d_kernel,
             d_inner,
+            state,
             padding,
             groups,
         } = self;
@@ -203,6 +170,7 @@ impl<T> CausalConv1d<T> {
                 casual_conv1d_b: TPTensor::from(casual_conv1d_b),
                 d_kernel,
                 d_inner,
+                state: state.map(RecurrentState::tensor_parallel),
                 padding,
                 groups,
             }
@@ -224,6 +192,7 @@ impl<T> NuralNetwork<T> for CausalConv1d<T> {
             casual_conv1d_b: b,
             d_kernel,
             d_inner,
+            state,
             padding,
             groups,
         } = self;
@@ -237,10 +206,17 @@ impl<T> NuralNetwork<T> for CausalConv1d<T> {
             [inner_size.clone(), kernel_size],
             w,
         );
-        let b = ctx.load_external("causal_conv1d_bias", dt, [inner_size], b);
+        let b = ctx.load_external("causal_conv1d_bias", dt, [inner_size.clone()], b);
         let arg = Arg::arr([groups, padding].map(|x| Arg::from(x as u64)));
-        let tensors = ctx.call("", "mamba-causal-conv1d", Some(arg), [x, w, b]);
-        destruct!([out] = tensors);
+        let state = state.map(|state| (state, vec![Dim::from(padding), inner_size]));
+        let out = call_with_state(
+            &mut ctx,
+            "state",
+            "mamba-causal-conv1d",
+            Some(arg),
+            vec![x, w, b],
+            state,
+        )?;
 
         Ok((ctx, out))
     }
@@ -255,6 +231,8 @@ pub struct SelectiveSSM<T> {
     pub dt_proj: Linear<T>,
     pub a: T,
     pub d: T,
+    /// 形状为 `[d_inner, d_state]` 的状态，即每个通道的隐状态。
+    pub state: Option<RecurrentState<T>>,
 }
 
 impl<T> SelectiveSSM<T> {
@@ -267,6 +245,7 @@ impl<T> SelectiveSSM<T> {
             dt_proj,
             a,
             d,
+            state,
         } = self;
 
         if dist.is_mono() {
@@ -278,6 +257,7 @@ impl<T> SelectiveSSM<T> {
                 dt_proj: dt_proj.parallel(TPAction::new(RowTPWeight, dist)),
                 a: TPTensor::from(a),
                 d: TPTensor::from(d),
+                state: state.map(RecurrentState::tensor_parallel),
             }
         } else {
             todo!();
@@ -299,6 +279,7 @@ impl<T> NuralNetwork<T> for SelectiveSSM<T> {
             dt_proj,
             a,
             d,
+            state,
         } = self;
         destruct!([x] = inputs);
         dims!([_l, d_in] = x);
@@ -314,8 +295,146 @@ impl<T> NuralNetwork<T> for SelectiveSSM<T> {
         destruct!([delta] = ctx.trap("dt-proj", dt_proj, [delta])?);
         let a = ctx.load_external("ssm_a", dt, [d_in.clone(), Dim::from(d_state)], a);
         let d = ctx.load_external("ssm_d", dt, [d_in.clone()], d);
-        let out = ctx.call("", "mamba-selective-scan", None, [x, delta, a, b, c, d])?;
+        let state = state.map(|state| (state, vec![d_in.clone(), Dim::from(d_state)]));
+        let out = call_with_state(
+            &mut ctx,
+            "state",
+            "mamba-selective-scan",
+            None,
+            vec![x, delta, a, b, c, d],
+            state,
+        )?;
 
         Ok((ctx, out))
     }
 }
+
+/// Mamba-2，每层都是 [`Mamba2Block`] 的解码器，混合层使用多头的 SSD 扫描。
+pub type Mamba2<T> = Decoder<T, Mamba2Block<T>>;
+
+#[derive(Clone)]
+pub struct Mamba2Block<T> {
+    pub mamba_norm: Normalization<T>,
+    pub mamba_mixer: Mamba2Mixer<T>,
+}
+
+/// Mamba-2 的混合层。
+///
+/// `in_proj` 依次输出门控 z、卷积输入 xBC 和每个头的 dt，xBC 卷积后拆分为 x、B、C，
+/// 每个头的 A、D 都是标量，B、C 分为 `n_group` 组，扫描结果经过门控的分组 RMS 归一化后输入 `out_proj`。
+#[derive(Clone)]
+pub struct Mamba2Mixer<T> {
+    pub d_inner: usize,
+    pub d_state: usize,
+    pub n_group: usize,
+    pub n_head: usize,
+    pub in_proj: Linear<T>,
+    /// 对 xBC 的卷积，通道数为 `d_inner + 2 * n_group * d_state`。
+    pub causal_conv1d: CausalConv1d<T>,
+    pub act: Activation,
+    /// 形状为 `[n_head]` 的 A，与数据类型一起给出，下同。
+    pub a: (DigitLayout, T),
+    /// 形状为 `[n_head]` 的 D。
+    pub d: (DigitLayout, T),
+    /// 形状为 `[n_head]` 的 dt 偏置。
+    pub dt_bias: (DigitLayout, T),
+    pub norm_epsilon: f64,
+    /// 形状为 `[d_inner]` 的归一化系数。
+    pub norm_scale: (DigitLayout, T),
+    pub out_proj: Linear<T>,
+    /// 形状为 `[n_head, d_inner / n_head, d_state]` 的状态，即每个头的隐状态。
+    pub state: Option<RecurrentState<T>>,
+}
+
+impl<T> DecoderBlock<T> for Mamba2Block<T> {
+    const INPUTS: &'static [BlockInput] = &[];
+}
+
+impl<T> NuralNetwork<T> for Mamba2Block<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            mamba_norm,
+            mamba_mixer,
+        } = self;
+        destruct!([x] = inputs);
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("rms-norm", mamba_norm, [x])?);
+        destruct!([x] = ctx.trap("mixer", mamba_mixer, [x, residual])?);
+
+        Ok((ctx, vec![x]))
+    }
+}
+
+impl<T> NuralNetwork<T> for Mamba2Mixer<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            d_inner,
+            d_state,
+            n_group,
+            n_head,
+            in_proj,
+            causal_conv1d,
+            act,
+            a,
+            d,
+            dt_bias,
+            norm_epsilon,
+            norm_scale,
+            out_proj,
+            state,
+        } = self;
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let residual = inputs.next();
+
+        let d_bc = n_group * d_state;
+        destruct!([zxbcdt] = ctx.trap("in-proj", in_proj, [x])?);
+        destruct!(
+            [z, xbc, delta] = zxbcdt.split(
+                "split-z-xbc-dt",
+                1,
+                [d_inner, d_inner + 2 * d_bc, n_head].map(Dim::from)
+            )?
+        );
+        destruct!([xbc] = ctx.trap("causal-conv1d", causal_conv1d, [xbc])?);
+        destruct!([xbc] = ctx.trap("silu", act, [xbc])?);
+        destruct!([x, b, c] = xbc.split("split-x-b-c", 1, [d_inner, d_bc, d_bc].map(Dim::from))?);
+
+        let [a, d, dt_bias] = [("ssm_a", a), ("ssm_d", d), ("ssm_dt_bias", dt_bias)]
+            .map(|(name, (dt, item))| ctx.load_external(name, dt, [Dim::from(n_head)], item));
+        let state = state.map(|state| {
+            let shape = [n_head, d_inner / n_head, d_state].map(Dim::from);
+            (state, shape.to_vec())
+        });
+        destruct!(
+            [y] = call_with_state(
+                &mut ctx,
+                "state",
+                "mamba-ssd-scan",
+                Some(Arg::int(n_group)),
+                vec![x, delta, a, b, c, d, dt_bias],
+                state,
+            )?
+        );
+
+        let (dt_norm, norm_scale) = norm_scale;
+        let scale = ctx.load_external("ssm_norm", dt_norm, [Dim::from(d_inner)], norm_scale);
+        let arg = Arg::dict([
+            ("epsilon".into(), norm_epsilon.into()),
+            ("groups".into(), Arg::int(n_group)),
+        ]);
+        destruct!([y] = ctx.call("", "gated-rms-norm", Some(arg), [y, z, scale])?);
+        let outputs = ctx.trap("out-proj", out_proj, std::iter::once(y).chain(residual))?;
+
+        Ok((ctx, outputs))
+    }
+}
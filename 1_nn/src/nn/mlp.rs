@@ -18,7 +18,9 @@ impl<T> Mlp<T> {
             up: up.parallel(match act {
                 Activation::SwiGLU => TPAction::new(FfnGateUp, dist),
                 Activation::SiLU => TPAction::new(FfnGateUp, dist),
-                Activation::GeLU => TPAction::new(ColumnTPWeight, dist),
+                Activation::GeLU | Activation::Tanh | Activation::Sigmoid | Activation::ReluSq => {
+                    TPAction::new(ColumnTPWeight, dist)
+                }
             }),
             act,
             down: down.parallel(TPAction::new(RowTPWeight, dist)),
@@ -1,18 +1,28 @@
 mod activation;
 mod attention;
+mod audio_encoder;
 mod cogvlm;
+mod decoder;
 mod distribution;
 mod embedding;
+mod encoder_decoder;
+mod hybrid;
+mod inputs;
 mod linear;
 mod llama;
+mod lora;
 mod mamba;
 mod merger;
 mod mlp;
+mod moe;
 mod normalization;
 mod output_head;
 mod patch_embd;
 mod qw2vl_mmproj;
 mod rwkv;
+mod rwkv6;
+mod rwkv7;
+mod state;
 mod transformer_blk;
 
 use crate::{
@@ -21,20 +31,31 @@ use crate::{
 };
 
 pub use activation::Activation;
-pub use attention::{Attention, RoPE};
+pub use attention::{Attention, CrossAttention, RoPE};
+pub use audio_encoder::{AudioEncoder, Conv1d, ConvStem};
 pub use cogvlm::CogVLM;
+pub use decoder::{BlockInput, Decoder, DecoderBlock};
 pub use distribution::{Distribution, TPAction, TPTensor, WeightType, weight_types};
 pub use embedding::{Embedding, Table};
+pub use encoder_decoder::{CrossTransformerBlk, Encoder, EncoderDecoder};
+pub use hybrid::{Ffn, FfnType, Hybrid, Layer, LayerType, Mixer, MixerType};
 pub use linear::Linear;
 pub use llama::LLaMA;
-pub use mamba::{CausalConv1d, Mamba, MambaBlock, MambaMixer, SelectiveSSM};
+pub use lora::Lora;
+pub use mamba::{
+    CausalConv1d, Mamba, Mamba2, Mamba2Block, Mamba2Mixer, MambaBlock, MambaMixer, SelectiveSSM,
+};
 pub use merger::Merger;
 pub use mlp::Mlp;
+pub use moe::Moe;
 pub use normalization::{Normalization, Type as NormType};
 pub use output_head::OutputHead;
 pub use patch_embd::PatchEmbd;
 pub use qw2vl_mmproj::Qwen2VLmmproj;
-pub use rwkv::{RWKV, RWKVBlock};
+pub use rwkv::{ChannelMix, RWKV, RWKVBlock, TimeMix};
+pub use rwkv6::{RWKV6, RWKV6Block, RWKV6ChannelMix, RWKV6TimeMix};
+pub use rwkv7::{LnX, RWKV7, RWKV7Block, RWKV7ChannelMix, RWKV7TimeMix};
+pub use state::RecurrentState;
 pub use transformer_blk::TransformerBlk;
 
 pub trait NuralNetwork<T>: Sized {
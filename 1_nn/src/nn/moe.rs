@@ -0,0 +1,96 @@
+use super::{Context, Linear, Mlp, NNError, NuralNetwork, Tensor, macros::*};
+use arg::Arg;
+
+/// 混合专家前馈网络，`gate` 为每个 token 打分选出 `n_act` 个专家，输出选中专家的加权和。
+///
+/// 专家数为 `gate` 的输出维度。所有专家的权重堆叠存储，`experts` 中线性层的 `shape`
+/// 是单个专家的形状，权重的形状为 `[n_expert, ..shape]`，每个 token 只经过选中的专家。
+#[derive(Clone)]
+pub struct Moe<T> {
+    pub gate: Linear<T>,
+    pub experts: Mlp<T>,
+    pub n_act: usize,
+}
+
+/// 堆叠的专家线性层，输入为 `[x, routes]`。
+struct Experts<T> {
+    n_expert: usize,
+    linear: Linear<T>,
+}
+
+impl<T> NuralNetwork<T> for Moe<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            gate,
+            experts: Mlp { up, act, down },
+            n_act,
+        } = self;
+        let [n_expert, _] = gate.shape;
+
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let residual = inputs.next();
+
+        destruct!([logits] = ctx.trap("gate", gate, [x.clone()])?);
+        destruct!([routes, weights] = ctx.call("", "moe-gate", Some(Arg::int(n_act)), [logits])?);
+
+        // [n, d] -> [n, k, di]
+        let up = Experts {
+            n_expert,
+            linear: up,
+        };
+        destruct!([x] = ctx.trap("ffn-up", up, [x, routes.clone()])?);
+        dims!([n, k, _] = x);
+        let [n, k] = [n.clone(), k.clone()];
+        let x = x.merge("", 0, 2)?;
+        destruct!([x] = ctx.trap("activation", act, [x])?);
+        let x = x.tile("", 0, [n, k])?;
+        // [n, k, di] -> [n, k, d]
+        let down = Experts {
+            n_expert,
+            linear: down,
+        };
+        destruct!([x] = ctx.trap("ffn-down", down, [x, routes])?);
+        destruct!([x] = ctx.call("", "moe-combine", None, [weights, x])?);
+
+        let outputs = match residual {
+            Some(residual) => ctx.call("", "add", None, [x, residual])?,
+            None => vec![x],
+        };
+        Ok((ctx, outputs))
+    }
+}
+
+impl<T> NuralNetwork<T> for Experts<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            n_expert,
+            linear:
+                Linear {
+                    dt,
+                    shape: [r, c],
+                    weight,
+                    bias,
+                    ..
+                },
+        } = self;
+
+        destruct!([x, routes] = inputs);
+        let w = ctx.load_external("weight", dt, [n_expert, r, c].map(Into::into), weight);
+        let mut inputs = vec![x, routes, w];
+        if let Some((dt, bias)) = bias {
+            inputs.push(ctx.load_external("bias", dt, [n_expert, r].map(Into::into), bias))
+        }
+        let outputs = ctx.call("", "moe-linear", None, inputs);
+
+        Ok((ctx, outputs?))
+    }
+}
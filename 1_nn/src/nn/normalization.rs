@@ -1,4 +1,5 @@
 ﻿use super::{Context, NNError, NuralNetwork, TPTensor, Tensor, macros::destruct};
+use arg::Arg;
 use tensor::digit_layout::DigitLayout;
 
 #[derive(Clone)]
@@ -20,6 +21,14 @@ pub enum Type<T> {
         dt_bias: DigitLayout,
         bias: T,
     },
+    /// 最后一维均分为 `groups` 组分别做层归一化。
+    GroupNorm {
+        groups: usize,
+        dt_scale: DigitLayout,
+        scale: T,
+        dt_bias: DigitLayout,
+        bias: T,
+    },
 }
 
 impl<T> Normalization<T> {
@@ -44,6 +53,19 @@ impl<T> Normalization<T> {
                     dt_bias,
                     bias: bias.into(),
                 },
+                Type::GroupNorm {
+                    groups,
+                    dt_scale,
+                    scale,
+                    dt_bias,
+                    bias,
+                } => Type::GroupNorm {
+                    groups,
+                    dt_scale,
+                    scale: scale.into(),
+                    dt_bias,
+                    bias: bias.into(),
+                },
             },
         }
     }
@@ -73,6 +95,21 @@ impl<T> NuralNetwork<T> for Normalization<T> {
                 let bias = ctx.load_external("bias", dt_bias, [d.into()], bias);
                 ctx.call("", "layer-norm", Some(epsilon.into()), [x, scale, bias])
             }
+            Type::GroupNorm {
+                groups,
+                dt_scale,
+                scale,
+                dt_bias,
+                bias,
+            } => {
+                let scale = ctx.load_external("scale", dt_scale, [d.into()], scale);
+                let bias = ctx.load_external("bias", dt_bias, [d.into()], bias);
+                let arg = Arg::dict([
+                    ("epsilon".into(), epsilon.into()),
+                    ("groups".into(), Arg::int(groups)),
+                ]);
+                ctx.call("", "group-norm", Some(arg), [x, scale, bias])
+            }
         };
 
         Ok((ctx, outputs?))
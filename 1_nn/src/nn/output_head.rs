@@ -32,3 +32,19 @@ impl<T> NuralNetwork<T> for OutputHead<T> {
         Ok((ctx, vec![x]))
     }
 }
+
+/// 有输出头时，从 `x` 中取出 `out_idx` 指定的 token 依次经过输出归一化和语言模型头，否则直接返回 `x`。
+pub(super) fn output<T>(
+    ctx: &mut Context<T>,
+    output_head: Option<OutputHead<T>>,
+    x: Tensor<T>,
+    out_idx: Option<Tensor<T>>,
+) -> Result<Tensor<T>, NNError> {
+    let Some(OutputHead { out_norm, lm_head }) = output_head else {
+        return Ok(x);
+    };
+    destruct!([x] = ctx.call("out-gather", "embedding", None, [x, out_idx.unwrap()])?);
+    destruct!([x] = ctx.trap("out-norm", out_norm, [x])?);
+    destruct!([x] = ctx.trap("lm-head", lm_head, [x])?);
+    Ok(x)
+}
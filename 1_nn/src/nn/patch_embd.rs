@@ -1,6 +1,6 @@
 use super::{Context, Distribution, NNError, NuralNetwork, TPTensor, Tensor, macros::destruct};
 use crate::macros::dims;
-use arg::Dim;
+use arg::{Arg, Dim};
 use tensor::digit_layout::DigitLayout;
 
 #[derive(Clone)]
@@ -41,7 +41,7 @@ impl<T> NuralNetwork<T> for PatchEmbd<T> {
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
         destruct!([x] = inputs);
 
-        dims!([n, _c, height, width] = x);
+        dims!([n, _c, _, _] = x);
         let Self {
             dt,
             shape,
@@ -62,19 +62,24 @@ impl<T> NuralNetwork<T> for PatchEmbd<T> {
             [m.clone(), ck.clone(), hk.clone(), wk.clone()],
             patch_embd1,
         );
+        // 步长与卷积核相同，每个 patch 互不重叠
+        let arg = Arg::dict([(
+            "stride".into(),
+            Arg::arr([shape[2], shape[3]].map(Arg::int)),
+        )]);
         let tensors = ctx
-            .call("", "conv", Some(false.into()), [x.clone(), w])
+            .call("", "conv", Some(arg.clone()), [x.clone(), w])
             .unwrap();
         destruct!([patch_embd] = tensors);
-        let tensors = ctx.call("", "conv", Some(false.into()), [x, w1]).unwrap();
+        let tensors = ctx.call("", "conv", Some(arg), [x, w1]).unwrap();
         destruct!([patch_embd1] = tensors);
         let tensors = ctx
             .call("", "add", None, [patch_embd, patch_embd1])
             .unwrap();
         destruct!([image_embd] = tensors);
 
-        let hp = height.clone() / hk.clone(); // h patches
-        let wp = width.clone() / wk.clone(); // w patches
+        dims!([_, _, hp, wp] = image_embd); // h patches, w patches
+        let (hp, wp) = (hp.clone(), wp.clone());
 
         // transpose: [n, m, hp, wp] -> [n, hp, wp, m]
         destruct!([image_embd] = image_embd.transpose("", vec![0, 2, 3, 1]));
@@ -1,14 +1,14 @@
 use super::{
-    Context, Distribution, Embedding, Linear, NNError, Normalization, NuralNetwork, TPAction,
-    TPTensor, Tensor, macros::destruct, output_head::OutputHead, weight_types::RowTPWeight,
+    BlockInput, Context, Decoder, DecoderBlock, Distribution, Embedding, Linear, NNError,
+    Normalization, NuralNetwork, OutputHead, RecurrentState, TPAction, TPTensor, Tensor,
+    macros::{destruct, dims},
+    state::call_with_state,
+    weight_types::RowTPWeight,
 };
+use arg::Dim;
 
-#[derive(Clone)]
-pub struct RWKV<T> {
-    pub embedding: Embedding<T>,
-    pub blks: Box<[RWKVBlock<T>]>,
-    pub output_head: Option<OutputHead<T>>,
-}
+/// RWKV，每层都是 [`RWKVBlock`] 的解码器。
+pub type RWKV<T> = Decoder<T, RWKVBlock<T>>;
 
 #[derive(Clone)]
 pub struct RWKVBlock<T> {
@@ -55,6 +55,10 @@ impl<T> RWKVBlock<T> {
     }
 }
 
+impl<T> DecoderBlock<T> for RWKVBlock<T> {
+    const INPUTS: &'static [BlockInput] = &[];
+}
+
 impl<T> NuralNetwork<T> for RWKVBlock<T> {
     fn launch(
         self,
@@ -94,6 +98,8 @@ pub struct TimeMix<T> {
     pub time_mix_v: T,
     pub time_mix_r: T,
     pub layer_id: usize, // 需要传递给底层算子
+    /// 形状为 `[batch, 4, d_model]` 的状态，依次为 token shift 和 wkv 的 a、b、p。
+    pub state: Option<RecurrentState<T>>,
 }
 
 impl<T> TimeMix<T> {
@@ -114,6 +120,7 @@ impl<T> TimeMix<T> {
             time_mix_v,
             time_mix_r,
             layer_id,
+            state: None,
         }
     }
 
@@ -126,6 +133,7 @@ impl<T> TimeMix<T> {
             time_mix_v,
             time_mix_r,
             layer_id,
+            state,
         } = self;
         TimeMix {
             k: k.parallel(TPAction::new(RowTPWeight, dist)),
@@ -135,6 +143,7 @@ impl<T> TimeMix<T> {
             time_mix_v: time_mix_v.into(),
             time_mix_r: time_mix_r.into(),
             layer_id,
+            state: state.map(RecurrentState::tensor_parallel),
         }
     }
 }
@@ -153,9 +162,14 @@ impl<T> NuralNetwork<T> for TimeMix<T> {
             time_mix_v,
             time_mix_r,
             layer_id,
+            state,
         } = self;
 
         destruct!([x] = inputs);
+        let state = state.map(|state| {
+            dims!([batch, _, d_model] = x);
+            (state, vec![batch.clone(), Dim::from(4), d_model.clone()])
+        });
 
         // 加载时间混合参数
         let time_mix_k = ctx.load_external("time_mix_k", x.dt(), x.shape(), time_mix_k);
@@ -167,13 +181,15 @@ impl<T> NuralNetwork<T> for TimeMix<T> {
         destruct!([v_out] = ctx.trap("linear-v", v, [x.clone()])?);
         destruct!([r_out] = ctx.trap("linear-r", r, [x.clone()])?);
 
-        // RWKV时间混合 - 状态由底层算子隐式管理
+        // RWKV时间混合 - 有状态时显式传入上一次的状态并输出新的状态
         destruct!(
-            [out] = ctx.call(
-                "",
+            [out] = call_with_state(
+                &mut ctx,
+                "state",
                 "rwkv-time-mix",
                 Some((layer_id as u64).into()), // 层ID作为参数传递给算子
-                [x, k_out, v_out, r_out, time_mix_k, time_mix_v, time_mix_r]
+                vec![x, k_out, v_out, r_out, time_mix_k, time_mix_v, time_mix_r],
+                state,
             )?
         );
 
@@ -189,6 +205,8 @@ pub struct ChannelMix<T> {
     pub time_mix_k: T,
     pub time_mix_r: T,
     pub layer_id: usize,
+    /// 形状为 `[batch, d_model]` 的状态，即 token shift。
+    pub state: Option<RecurrentState<T>>,
 }
 
 impl<T> ChannelMix<T> {
@@ -207,6 +225,7 @@ impl<T> ChannelMix<T> {
             time_mix_k,
             time_mix_r,
             layer_id,
+            state: None,
         }
     }
 
@@ -218,6 +237,7 @@ impl<T> ChannelMix<T> {
             time_mix_k,
             time_mix_r,
             layer_id,
+            state,
         } = self;
         ChannelMix {
             k: k.parallel(TPAction::new(RowTPWeight, dist)),
@@ -226,6 +246,7 @@ impl<T> ChannelMix<T> {
             time_mix_k: time_mix_k.into(),
             time_mix_r: time_mix_r.into(),
             layer_id,
+            state: state.map(RecurrentState::tensor_parallel),
         }
     }
 }
@@ -243,9 +264,14 @@ impl<T> NuralNetwork<T> for ChannelMix<T> {
             time_mix_k,
             time_mix_r,
             layer_id,
+            state,
         } = self;
 
         destruct!([x] = inputs);
+        let state = state.map(|state| {
+            dims!([batch, _, d_model] = x);
+            (state, vec![batch.clone(), d_model.clone()])
+        });
 
         let time_mix_k = ctx.load_external("time_mix_k", x.dt(), x.shape(), time_mix_k);
         let time_mix_r = ctx.load_external("time_mix_r", x.dt(), x.shape(), time_mix_r);
@@ -253,13 +279,15 @@ impl<T> NuralNetwork<T> for ChannelMix<T> {
         destruct!([k_out] = ctx.trap("linear-k", k, [x.clone()])?);
         destruct!([r_out] = ctx.trap("linear-r", r, [x.clone()])?);
 
-        // RWKV通道混合 - 状态由底层算子隐式管理
+        // RWKV通道混合 - 有状态时显式传入上一次的状态并输出新的状态
         destruct!(
-            [mixed] = ctx.call(
-                "",
+            [mixed] = call_with_state(
+                &mut ctx,
+                "state",
                 "rwkv-channel-mix",
                 Some((layer_id as u64).into()),
-                [x, k_out, r_out, time_mix_k, time_mix_r]
+                vec![x, k_out, r_out, time_mix_k, time_mix_r],
+                state,
             )?
         );
 
@@ -313,39 +341,3 @@ impl<T> RWKV<T> {
         }
     }
 }
-
-impl<T> NuralNetwork<T> for RWKV<T> {
-    fn launch(
-        self,
-        inputs: impl IntoIterator<Item = Tensor<T>>,
-        mut ctx: Context<T>,
-    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
-        let Self {
-            embedding,
-            blks,
-            output_head,
-        } = self;
-
-        let mut inputs = inputs.into_iter();
-        let tokens = inputs.next().unwrap();
-
-        destruct!([x] = ctx.trap("embedding", embedding, [tokens])?);
-
-        let x = blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
-            destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x])?);
-            Ok(x)
-        })?;
-
-        let x = if let Some(output_head) = output_head {
-            let out_idx = inputs.next().unwrap();
-            destruct!([x] = ctx.call("out-gather", "embedding", None, [x, out_idx])?);
-            destruct!([x] = ctx.trap("out-norm", output_head.out_norm, [x])?);
-            destruct!([x] = ctx.trap("lm-head", output_head.lm_head, [x])?);
-            x
-        } else {
-            x
-        };
-
-        Ok((ctx, vec![x]))
-    }
-}
@@ -0,0 +1,220 @@
+use super::{
+    Activation, BlockInput, Context, Decoder, DecoderBlock, Linear, Lora, NNError, Normalization,
+    NuralNetwork, RecurrentState, Tensor, macros::destruct, state::call_with_state,
+};
+use arg::{Arg, Dim};
+use tensor::digit_layout::DigitLayout;
+
+/// RWKV-6，token shift 的插值系数和 wkv 的衰减都由输入数据决定，wkv 按头计算。
+///
+/// 每层都是 [`RWKV6Block`] 的解码器，词嵌入之后的 ln0 为 [`Embedding::norm`](super::Embedding::norm)。
+pub type RWKV6<T> = Decoder<T, RWKV6Block<T>>;
+
+#[derive(Clone)]
+pub struct RWKV6Block<T> {
+    pub ln1: Normalization<T>,
+    pub time_mix: RWKV6TimeMix<T>,
+    pub ln2: Normalization<T>,
+    pub channel_mix: RWKV6ChannelMix<T>,
+}
+
+#[derive(Clone)]
+pub struct RWKV6TimeMix<T> {
+    pub dt: DigitLayout,
+    pub d: usize,
+    pub head_size: usize,
+    /// 数据相关 token shift 的低秩维度 `r`。
+    pub lerp_rank: usize,
+    /// 形状为 `[1, d]` 的基础插值系数。
+    pub lerp_x: T,
+    /// 形状为 `[5r, d]` 的低秩下投影。
+    pub lerp_w1: T,
+    /// 形状为 `[5, d, r]` 的低秩上投影。
+    pub lerp_w2: T,
+    /// 形状为 `[5, d]` 的 w、k、v、r、g 插值系数。
+    pub lerp_fused: T,
+    /// 数据相关的衰减，上投影的偏置为基础衰减。
+    pub decay: Lora<T>,
+    /// 形状为 `[nh, hs]` 的当前 token 奖励。
+    pub first: T,
+    pub r: Linear<T>,
+    pub k: Linear<T>,
+    pub v: Linear<T>,
+    pub g: Linear<T>,
+    /// 按头分组的组归一化。
+    pub ln_x: Normalization<T>,
+    pub output: Linear<T>,
+    /// 形状为 `[1, d]` 的状态，即 token shift。
+    pub shift_state: Option<RecurrentState<T>>,
+    /// 形状为 `[nh, hs, hs]` 的状态，即每个头的 wkv 矩阵。
+    pub wkv_state: Option<RecurrentState<T>>,
+}
+
+#[derive(Clone)]
+pub struct RWKV6ChannelMix<T> {
+    pub dt: DigitLayout,
+    pub d: usize,
+    /// 形状为 `[1, d]` 的 k 插值系数。
+    pub lerp_k: T,
+    /// 形状为 `[1, d]` 的 r 插值系数。
+    pub lerp_r: T,
+    pub k: Linear<T>,
+    pub v: Linear<T>,
+    pub r: Linear<T>,
+    /// 形状为 `[1, d]` 的状态，即 token shift。
+    pub state: Option<RecurrentState<T>>,
+}
+
+impl<T> DecoderBlock<T> for RWKV6Block<T> {
+    const INPUTS: &'static [BlockInput] = &[];
+}
+
+impl<T> NuralNetwork<T> for RWKV6Block<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            ln1,
+            time_mix,
+            ln2,
+            channel_mix,
+        } = self;
+
+        destruct!([x] = inputs);
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("ln1", ln1, [x])?);
+        destruct!([x] = ctx.trap("time-mix", time_mix, [x])?);
+        let x = ctx.call("add", "add", None, [x, residual])?.remove(0);
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("ln2", ln2, [x])?);
+        destruct!([x] = ctx.trap("channel-mix", channel_mix, [x])?);
+        let x = ctx.call("add", "add", None, [x, residual])?.remove(0);
+
+        Ok((ctx, vec![x]))
+    }
+}
+
+impl<T> NuralNetwork<T> for RWKV6TimeMix<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            dt,
+            d,
+            head_size,
+            lerp_rank,
+            lerp_x,
+            lerp_w1,
+            lerp_w2,
+            lerp_fused,
+            decay,
+            first,
+            r,
+            k,
+            v,
+            g,
+            ln_x,
+            output,
+            shift_state,
+            wkv_state,
+        } = self;
+
+        destruct!([x] = inputs);
+        let nh = r.shape[0] / head_size;
+
+        // 数据相关的 token shift
+        let lerp_x = ctx.load_external("lerp_x", dt, [1, d].map(Dim::from), lerp_x);
+        let lerp_w1 = ctx.load_external("lerp_w1", dt, [lerp_rank * 5, d].map(Dim::from), lerp_w1);
+        let lerp_w2 = ctx.load_external("lerp_w2", dt, [5, d, lerp_rank].map(Dim::from), lerp_w2);
+        let lerp_fused = ctx.load_external("lerp_fused", dt, [5, d].map(Dim::from), lerp_fused);
+        let shift_state = shift_state.map(|state| (state, [1, d].map(Dim::from).to_vec()));
+        destruct!(
+            [xw, xk, xv, xr, xg] = call_with_state(
+                &mut ctx,
+                "shift_state",
+                "rwkv6-ddlerp",
+                None,
+                vec![x, lerp_x, lerp_w1, lerp_w2, lerp_fused],
+                shift_state,
+            )?
+        );
+
+        destruct!([r] = ctx.trap("receptance", r, [xr])?);
+        destruct!([k] = ctx.trap("key", k, [xk])?);
+        destruct!([v] = ctx.trap("value", v, [xv])?);
+        destruct!([g] = ctx.trap("gate", g, [xg])?);
+        destruct!([g] = ctx.trap("silu", Activation::SiLU, [g])?);
+        destruct!([w] = ctx.trap("decay", decay, [xw])?);
+
+        // 多头 wkv
+        let first = ctx.load_external("first", dt, [nh, head_size].map(Dim::from), first);
+        let wkv_state =
+            wkv_state.map(|state| (state, [nh, head_size, head_size].map(Dim::from).to_vec()));
+        destruct!(
+            [x] = call_with_state(
+                &mut ctx,
+                "wkv_state",
+                "rwkv6-wkv",
+                None,
+                vec![r, k, v, w, first],
+                wkv_state,
+            )?
+        );
+
+        destruct!([x] = ctx.trap("ln-x", ln_x, [x])?);
+        destruct!([x] = ctx.call("gate", "element-mul", None, [x, g])?);
+        destruct!([x] = ctx.trap("output", output, [x])?);
+
+        Ok((ctx, vec![x]))
+    }
+}
+
+impl<T> NuralNetwork<T> for RWKV6ChannelMix<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            dt,
+            d,
+            lerp_k,
+            lerp_r,
+            k,
+            v,
+            r,
+            state,
+        } = self;
+
+        destruct!([x] = inputs);
+
+        let lerp_k = ctx.load_external("lerp_k", dt, [1, d].map(Dim::from), lerp_k);
+        let lerp_r = ctx.load_external("lerp_r", dt, [1, d].map(Dim::from), lerp_r);
+        let state = state.map(|state| (state, [1, d].map(Dim::from).to_vec()));
+        destruct!(
+            [xk, xr] = call_with_state(
+                &mut ctx,
+                "state",
+                "rwkv-lerp",
+                Some(Arg::int(2)),
+                vec![x, lerp_k, lerp_r],
+                state,
+            )?
+        );
+
+        destruct!([k] = ctx.trap("key", k, [xk])?);
+        destruct!([k] = ctx.trap("relu-sq", Activation::ReluSq, [k])?);
+        destruct!([v] = ctx.trap("value", v, [k])?);
+        destruct!([r] = ctx.trap("receptance", r, [xr])?);
+        destruct!([r] = ctx.trap("sigmoid", Activation::Sigmoid, [r])?);
+        destruct!([x] = ctx.call("gate", "element-mul", None, [v, r])?);
+
+        Ok((ctx, vec![x]))
+    }
+}
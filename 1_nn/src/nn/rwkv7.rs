@@ -0,0 +1,251 @@
+use super::{
+    Activation, BlockInput, Context, Decoder, DecoderBlock, Linear, Lora, NNError, Normalization,
+    NuralNetwork, RecurrentState, Tensor, macros::destruct, state::call_with_state,
+};
+use arg::{Arg, Dim};
+use std::iter::once;
+use tensor::digit_layout::DigitLayout;
+
+/// RWKV-7，wkv 的状态按广义 delta 规则更新，第一层的 value 作为残差传递给之后的每一层。
+///
+/// 每层都是 [`RWKV7Block`] 的解码器，词嵌入之后的 ln0 为 [`Embedding::norm`](super::Embedding::norm)。
+pub type RWKV7<T> = Decoder<T, RWKV7Block<T>>;
+
+/// 输入为 `[x]` 或 `[x, v_first]`，输出为 `[x, v_first]`，`v_first` 由解码器传给下一层。
+#[derive(Clone)]
+pub struct RWKV7Block<T> {
+    pub ln1: Normalization<T>,
+    pub time_mix: RWKV7TimeMix<T>,
+    pub ln2: Normalization<T>,
+    pub channel_mix: RWKV7ChannelMix<T>,
+}
+
+/// 输入为 `[x]` 或 `[x, v_first]`，输出为 `[x, v_first]`，没有 `v_first` 时以本层的 value 作为 `v_first`。
+#[derive(Clone)]
+pub struct RWKV7TimeMix<T> {
+    pub dt: DigitLayout,
+    pub d: usize,
+    pub head_size: usize,
+    /// 形状为 `[6, d]` 的 r、w、k、v、a、g 插值系数。
+    pub lerp_fused: T,
+    pub r: Linear<T>,
+    pub k: Linear<T>,
+    pub v: Linear<T>,
+    /// 衰减，上投影的偏置为基础衰减。
+    pub w: Lora<T>,
+    /// 上下文学习率，上投影的偏置为基础学习率。
+    pub a: Lora<T>,
+    /// value 残差的混合系数，第一层没有。
+    pub v_mix: Option<Lora<T>>,
+    /// 输出门。
+    pub g: Lora<T>,
+    /// 形状为 `[d]` 的 k 归一化系数。
+    pub k_k: T,
+    /// 形状为 `[d]` 的 k 调整系数。
+    pub k_a: T,
+    /// 形状为 `[nh, hs]` 的 bonus 系数。
+    pub r_k: T,
+    /// 按头分组的组归一化。
+    pub ln_x: LnX<T>,
+    pub output: Linear<T>,
+    /// 形状为 `[1, d]` 的状态，即 token shift。
+    pub shift_state: Option<RecurrentState<T>>,
+    /// 形状为 `[nh, hs, hs]` 的状态，即每个头的 wkv 矩阵。
+    pub wkv_state: Option<RecurrentState<T>>,
+}
+
+/// 融合在 wkv 算子中的组归一化参数。
+#[derive(Clone)]
+pub struct LnX<T> {
+    pub epsilon: f64,
+    pub scale: T,
+    pub bias: T,
+}
+
+#[derive(Clone)]
+pub struct RWKV7ChannelMix<T> {
+    pub dt: DigitLayout,
+    pub d: usize,
+    /// 形状为 `[1, d]` 的 k 插值系数。
+    pub lerp_k: T,
+    pub k: Linear<T>,
+    pub v: Linear<T>,
+    /// 形状为 `[1, d]` 的状态，即 token shift。
+    pub state: Option<RecurrentState<T>>,
+}
+
+impl<T> DecoderBlock<T> for RWKV7Block<T> {
+    const INPUTS: &'static [BlockInput] = &[];
+}
+
+impl<T> NuralNetwork<T> for RWKV7Block<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            ln1,
+            time_mix,
+            ln2,
+            channel_mix,
+        } = self;
+
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let v_first = inputs.next();
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("ln1", ln1, [x])?);
+        destruct!([x, v_first] = ctx.trap("time-mix", time_mix, once(x).chain(v_first))?);
+        let x = ctx.call("add", "add", None, [x, residual])?.remove(0);
+
+        let residual = x.clone();
+        destruct!([x] = ctx.trap("ln2", ln2, [x])?);
+        destruct!([x] = ctx.trap("channel-mix", channel_mix, [x])?);
+        let x = ctx.call("add", "add", None, [x, residual])?.remove(0);
+
+        Ok((ctx, vec![x, v_first]))
+    }
+}
+
+impl<T> NuralNetwork<T> for RWKV7TimeMix<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            dt,
+            d,
+            head_size,
+            lerp_fused,
+            r,
+            k,
+            v,
+            w,
+            a,
+            v_mix,
+            g,
+            k_k,
+            k_a,
+            r_k,
+            ln_x,
+            output,
+            shift_state,
+            wkv_state,
+        } = self;
+
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let v_first = inputs.next();
+        let d_attn = r.shape[0];
+        let nh = d_attn / head_size;
+
+        // token shift
+        let lerp_fused = ctx.load_external("lerp_fused", dt, [6, d].map(Dim::from), lerp_fused);
+        let mut lerp = vec![x];
+        lerp.extend(lerp_fused.split("split-lerp", 0, [1; 6].map(Dim::from))?);
+        let shift_state = shift_state.map(|state| (state, [1, d].map(Dim::from).to_vec()));
+        destruct!(
+            [xr, xw, xk, xv, xa, xg] = call_with_state(
+                &mut ctx,
+                "shift_state",
+                "rwkv-lerp",
+                Some(Arg::int(6)),
+                lerp,
+                shift_state,
+            )?
+        );
+
+        destruct!([r] = ctx.trap("receptance", r, [xr])?);
+        destruct!([w] = ctx.trap("w", w, [xw])?);
+        destruct!([k] = ctx.trap("key", k, [xk])?);
+        destruct!([v] = ctx.trap("value", v, [xv.clone()])?);
+        destruct!([a] = ctx.trap("a", a, [xa])?);
+        destruct!([g] = ctx.trap("g", g, [xg])?);
+
+        // 第一层的 value 作为之后每一层的残差
+        let (v, v_first) = match v_first {
+            Some(v_first) => {
+                let v = match v_mix {
+                    Some(v_mix) => {
+                        destruct!([gate] = ctx.trap("v-mix", v_mix, [xv])?);
+                        let inputs = [v, v_first.clone(), gate];
+                        ctx.call("", "rwkv7-v-mix", None, inputs)?.remove(0)
+                    }
+                    None => v,
+                };
+                (v, v_first)
+            }
+            None => (v.clone(), v),
+        };
+
+        // wkv7
+        let k_k = ctx.load_external("k_k", dt, [d_attn.into()], k_k);
+        let k_a = ctx.load_external("k_a", dt, [d_attn.into()], k_a);
+        let r_k = ctx.load_external("r_k", dt, [nh, head_size].map(Dim::from), r_k);
+        let LnX {
+            epsilon,
+            scale,
+            bias,
+        } = ln_x;
+        let scale = ctx.load_external("ln_x.scale", dt, [d_attn.into()], scale);
+        let bias = ctx.load_external("ln_x.bias", dt, [d_attn.into()], bias);
+        let wkv_state =
+            wkv_state.map(|state| (state, [nh, head_size, head_size].map(Dim::from).to_vec()));
+        destruct!(
+            [x] = call_with_state(
+                &mut ctx,
+                "wkv_state",
+                "rwkv7-time-mix",
+                Some(epsilon.into()),
+                vec![r, w, k, v, a, k_k, k_a, r_k, scale, bias],
+                wkv_state,
+            )?
+        );
+
+        destruct!([x] = ctx.call("gate", "element-mul", None, [x, g])?);
+        destruct!([x] = ctx.trap("output", output, [x])?);
+
+        Ok((ctx, vec![x, v_first]))
+    }
+}
+
+impl<T> NuralNetwork<T> for RWKV7ChannelMix<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            dt,
+            d,
+            lerp_k,
+            k,
+            v,
+            state,
+        } = self;
+
+        destruct!([x] = inputs);
+
+        let lerp_k = ctx.load_external("lerp_k", dt, [1, d].map(Dim::from), lerp_k);
+        let state = state.map(|state| (state, [1, d].map(Dim::from).to_vec()));
+        destruct!(
+            [xk] = call_with_state(
+                &mut ctx,
+                "state",
+                "rwkv-lerp",
+                Some(Arg::int(1)),
+                vec![x, lerp_k],
+                state,
+            )?
+        );
+
+        destruct!([k] = ctx.trap("key", k, [xk])?);
+        destruct!([k] = ctx.trap("relu-sq", Activation::ReluSq, [k])?);
+        destruct!([x] = ctx.trap("value", v, [k])?);
+
+        Ok((ctx, vec![x]))
+    }
+}
@@ -0,0 +1,43 @@
+use super::{Context, NNError, TPTensor, Tensor};
+use arg::{Arg, Dim};
+
+/// 循环网络在两次调用之间传递的状态。
+#[derive(Clone)]
+pub struct RecurrentState<T> {
+    /// 上一次调用后的状态。
+    pub input: T,
+    /// 本次调用后的状态。
+    pub output: T,
+}
+
+impl<T> RecurrentState<T> {
+    pub fn tensor_parallel(self) -> RecurrentState<TPTensor<T>> {
+        let Self { input, output } = self;
+        RecurrentState {
+            input: input.into(),
+            output: output.into(),
+        }
+    }
+}
+
+/// 调用带有可选状态的算子。
+///
+/// 有状态时，形状为 `shape` 的状态输入以 `name` 为名加载并追加到 `inputs` 之后，数据类型与第一个输入相同，
+/// 算子的最后一个输出绑定为状态输出，不出现在返回值中。
+pub(super) fn call_with_state<T>(
+    ctx: &mut Context<T>,
+    name: &str,
+    op: &str,
+    arg: Option<Arg>,
+    mut inputs: Vec<Tensor<T>>,
+    state: Option<(RecurrentState<T>, Vec<Dim>)>,
+) -> Result<Vec<Tensor<T>>, NNError> {
+    let Some((RecurrentState { input, output }, shape)) = state else {
+        return ctx.call("", op, arg, inputs);
+    };
+    let dt = inputs[0].dt();
+    inputs.push(ctx.load_external(name, dt, shape, input));
+    let mut outputs = ctx.call("", op, arg, inputs)?;
+    ctx.bind_external(outputs.pop().unwrap(), output);
+    Ok(outputs)
+}
@@ -1,24 +1,25 @@
 ﻿use super::{
-    Attention, Context, Distribution, Mlp, NNError, Normalization, NuralNetwork, TPTensor, Tensor,
-    macros::destruct,
+    Attention, BlockInput, Context, DecoderBlock, Distribution, Mlp, NNError, Normalization,
+    NuralNetwork, TPTensor, Tensor, macros::destruct,
 };
 
+/// Transformer 块，前馈网络默认为 [`Mlp`]，也可以是 [`Moe`](super::Moe) 等其他网络。
 #[derive(Clone)]
-pub struct TransformerBlk<T> {
+pub struct TransformerBlk<T, Ffn = Mlp<T>> {
     pub attn_norm: Normalization<T>,
     pub attn: Attention<T>,
     pub ffn_norm: Normalization<T>,
-    pub ffn: Mlp<T>,
+    pub ffn: Ffn,
     pub all_reduce: bool,
 }
 
-impl<T> TransformerBlk<T> {
+impl<T, Ffn> TransformerBlk<T, Ffn> {
     #[inline]
     pub const fn new(
         attn_norm: Normalization<T>,
         attn: Attention<T>,
         ffn_norm: Normalization<T>,
-        ffn: Mlp<T>,
+        ffn: Ffn,
     ) -> Self {
         Self {
             attn_norm,
@@ -29,6 +30,14 @@ impl<T> TransformerBlk<T> {
         }
     }
 
+    /// 去掉自注意力的因果掩码，用于编码器。
+    pub fn bidirectional(mut self) -> Self {
+        self.attn.causal = false;
+        self
+    }
+}
+
+impl<T> TransformerBlk<T> {
     pub fn tensor_parallel(self, dist: Distribution) -> TransformerBlk<TPTensor<T>> {
         let Self {
             attn_norm,
@@ -47,11 +56,16 @@ impl<T> TransformerBlk<T> {
     }
 }
 
-impl<T> NuralNetwork<T> for TransformerBlk<T> {
+impl<T, Ffn: NuralNetwork<T>> DecoderBlock<T> for TransformerBlk<T, Ffn> {
+    const INPUTS: &'static [BlockInput] = &[BlockInput::Pos];
+    const REPEAT: bool = true;
+}
+
+impl<T, Ffn: NuralNetwork<T>> NuralNetwork<T> for TransformerBlk<T, Ffn> {
     fn launch(
         self,
         inputs: impl IntoIterator<Item = Tensor<T>>,
-        mut ctx: Context<T>,
+        ctx: Context<T>,
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
         let Self {
             attn_norm,
@@ -62,21 +76,52 @@ impl<T> NuralNetwork<T> for TransformerBlk<T> {
         } = self;
 
         destruct!([x, pos] = inputs);
-        let residual = x.clone();
-        let tensors = ctx.trap("attn-norm", attn_norm, [x])?;
-        destruct!([x] = tensors);
-        let tensors = ctx.trap("attn", attn, [x, pos, residual])?;
-        let tensors = if all_reduce {
-            ctx.call("", "all-reduce", Some("sum".into()), tensors)?
-        } else {
-            tensors
+        let attn = PreNorm {
+            names: ["attn-norm", "attn"],
+            norm: attn_norm,
+            body: attn,
+            all_reduce,
+        };
+        let (ctx, tensors) = attn.launch([x, pos], ctx)?;
+        let ffn = PreNorm {
+            names: ["ffn-norm", "ffn"],
+            norm: ffn_norm,
+            body: ffn,
+            all_reduce,
         };
+        ffn.launch(tensors, ctx)
+    }
+}
+
+/// 预归一化的子块，输入为 `[x, ..]`，`x` 归一化后与其余输入以及残差 `x` 一起送入自带残差的 `body`。
+///
+/// `names` 为归一化和 `body` 的名字。张量并行时 `body` 的输出是部分和，需要 all-reduce。
+pub(super) struct PreNorm<T, Body> {
+    pub names: [&'static str; 2],
+    pub norm: Normalization<T>,
+    pub body: Body,
+    pub all_reduce: bool,
+}
+
+impl<T, Body: NuralNetwork<T>> NuralNetwork<T> for PreNorm<T, Body> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            names: [norm_name, body_name],
+            norm,
+            body,
+            all_reduce,
+        } = self;
 
-        destruct!([x] = tensors);
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
         let residual = x.clone();
-        let tensors = ctx.trap("ffn-norm", ffn_norm, [x])?;
-        destruct!([x] = tensors);
-        let tensors = ctx.trap("ffn", ffn, [x, residual])?;
+        destruct!([x] = ctx.trap(norm_name, norm, [x])?);
+        let inputs = std::iter::once(x).chain(inputs).chain([residual]);
+        let tensors = ctx.trap(body_name, body, inputs.collect::<Vec<_>>())?;
         let tensors = if all_reduce {
             ctx.call("", "all-reduce", Some("sum".into()), tensors)?
         } else {
@@ -20,6 +20,10 @@ impl Operator for SwiGLU {
 
         Ok(vec![TensorMeta::new(gate.dt, [n_up, d_up])])
     }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0), (0, 1)]
+    }
 }
 pub struct SiLU;
 
@@ -34,6 +38,10 @@ impl Operator for SiLU {
 
         Ok(vec![x.clone()])
     }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
 }
 
 pub struct GeLU;
@@ -49,4 +57,57 @@ impl Operator for GeLU {
 
         Ok(vec![x.clone()])
     }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
+}
+
+/// 一元逐元素激活，输入输出形状相同。
+fn unary(inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+    if args.is_some() {
+        return Err(OpError::ArgError);
+    }
+
+    destruct!([x] = inputs);
+    dims!([_n, _d] = x);
+
+    Ok(vec![x.clone()])
+}
+
+pub struct Tanh;
+
+impl Operator for Tanh {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        unary(inputs, args)
+    }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
+}
+
+pub struct Sigmoid;
+
+impl Operator for Sigmoid {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        unary(inputs, args)
+    }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
+}
+
+/// `relu(x)^2`。
+pub struct ReluSq;
+
+impl Operator for ReluSq {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        unary(inputs, args)
+    }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
 }
@@ -1,5 +1,5 @@
 ﻿use super::{OpError, Operator};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, LayoutReq, TensorMeta};
 
 pub struct AllReduce;
 
@@ -14,4 +14,8 @@ impl Operator for AllReduce {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::ROW_MAJOR]
+    }
 }
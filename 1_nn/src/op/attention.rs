@@ -1,12 +1,61 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, Dim, LayoutReq, TensorMeta};
 use arg::make_eq;
 
+/// 注意力，输入为 `[q, k, v]`，参数为 `{dh, mask}`。
+///
+/// 自注意力中 q、k、v 来自同一序列，长度必须相同；
+/// 交叉注意力中 k、v 来自编码器，长度可以与 q 不同。
 pub struct Attention;
 
+/// 注意力的掩码。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mask {
+    /// 因果掩码，用于解码器的自注意力。
+    Causal,
+    /// 不加掩码的自注意力，用于编码器。
+    Bidirectional,
+    /// 不加掩码的交叉注意力。
+    None,
+}
+
+impl Mask {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Causal => "causal",
+            Self::Bidirectional => "bidirectional",
+            Self::None => "none",
+        }
+    }
+
+    fn from_arg(arg: &Arg) -> Option<Self> {
+        match arg {
+            Arg::Str("causal") => Some(Self::Causal),
+            Arg::Str("bidirectional") => Some(Self::Bidirectional),
+            Arg::Str("none") => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
+impl Attention {
+    /// 构造注意力的参数。
+    pub fn arg(dh: Dim, mask: Mask) -> Arg {
+        Arg::dict([
+            ("dh".into(), dh.into()),
+            ("mask".into(), Arg::Str(mask.as_str())),
+        ])
+    }
+}
+
 impl Operator for Attention {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let Some(Arg::Dim(_dh)) = args else {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let (Some(Arg::Dim(_dh)), Some(mask)) =
+            (args.get("dh"), args.get("mask").and_then(Mask::from_arg))
+        else {
             return Err(OpError::ArgError);
         };
 
@@ -16,12 +65,20 @@ impl Operator for Attention {
                 dims!([n_k, _dk] = k);
                 dims!([n_v, _dv] = v);
 
-                // Check if all inputs have the same batch size
-                let n_q = make_eq(&[&q.shape[0], n_q, n_k, n_v]).ok_or(OpError::ShapeMismatch)?;
+                // k 和 v 的长度相同，输出与 q 的长度相同
+                let n_kv = make_eq(&[n_k, n_v]).ok_or(OpError::ShapeMismatch)?;
+                // 自注意力的 q 与 k、v 长度相同
+                if mask != Mask::None {
+                    make_eq(&[n_q, &n_kv]).ok_or(OpError::ShapeMismatch)?;
+                }
 
-                Ok(vec![TensorMeta::new(q.dt, [n_q, _dq.clone()])])
+                Ok(vec![TensorMeta::new(q.dt, [n_q.clone(), _dq.clone()])])
             }
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST; 3]
+    }
 }
@@ -11,10 +11,16 @@ impl Operator for Concat {
         };
         let axis = *axis as usize;
 
-        // TODO 判定其他维度相等
+        let [first, ..] = inputs else {
+            return Err(OpError::ArityError);
+        };
+        let ndim = first.shape.len();
+        if axis >= ndim || inputs.iter().any(|t| t.shape.len() != ndim) {
+            return Err(OpError::ShapeError);
+        }
 
-        let dt = inputs[0].dt;
-        let concat_shape = (0..inputs[0].shape.len())
+        let dt = first.dt;
+        let concat_shape = (0..ndim)
             .map(|i| {
                 if i == axis {
                     Ok(inputs
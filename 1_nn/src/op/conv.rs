@@ -1,54 +1,129 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, Dim, LayoutReq, TensorMeta};
 use arg::make_eq;
+use std::collections::HashMap;
 
+/// N 维卷积，输入为 `[x [n, c, ..spatial], w [m, c / groups, ..kernel]]` 或再加上 `b [m]`。
+///
+/// 参数为 `{stride, padding, dilation, groups}`，前三项是每个空间维度的整数数组，缺省时分别为 1、0、1，
+/// `groups` 缺省为 1。每个空间维度的输出长度为 `(len + 2 * padding - dilation * (k - 1) - 1) / stride + 1`。
 pub struct Conv;
 
 impl Operator for Conv {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let Some(Arg::Bool(bias)) = args else {
+        let Some(Arg::Dict(args)) = args else {
             return Err(OpError::ArgError);
         };
-        match inputs {
-            [x, w] if !*bias => {
-                dims!([n, c, height, width] = x);
-                dims!([m, ck, hk, wk] = w);
 
-                // Check if channel match
-                if c != ck {
-                    return Err(OpError::ShapeMismatch);
-                }
+        let (x, w, b) = match inputs {
+            [x, w] => (x, w, None),
+            [x, w, b] => (x, w, Some(b)),
+            _ => return Err(OpError::ShapeError),
+        };
+        if w.dt != x.dt {
+            return Err(OpError::DataTypeMismatch);
+        }
+        let [n, c, spatial @ ..] = &*x.shape else {
+            return Err(OpError::ShapeError);
+        };
+        let [m, ck, kernel @ ..] = &*w.shape else {
+            return Err(OpError::ShapeError);
+        };
+        if spatial.is_empty() || kernel.len() != spatial.len() {
+            return Err(OpError::ShapeError);
+        }
 
-                let ny = n.clone();
-                let my = m.clone();
-                let hy = height.clone() / hk.clone();
-                let wy = width.clone() / wk.clone();
+        let ndim = spatial.len();
+        let stride = per_dim(args, "stride", ndim, 1)?;
+        let padding = per_dim(args, "padding", ndim, 0)?;
+        let dilation = per_dim(args, "dilation", ndim, 1)?;
+        let groups = match args.get("groups") {
+            Some(&Arg::Int(groups)) => groups as usize,
+            None => 1,
+            Some(_) => return Err(OpError::ArgError),
+        };
+        if groups == 0 || stride.contains(&0) || dilation.contains(&0) {
+            return Err(OpError::ArgError);
+        }
 
-                Ok(vec![TensorMeta::new(x.dt, [ny, my, hy, wy])])
-            }
-            [x, w, b] if *bias => {
-                dims!([n, c, height, width] = x);
-                dims!([m, ck, hk, wk] = w);
+        // 每组的输入通道数与权重的输入通道数相同
+        make_eq(&[c, &(ck.clone() * groups)]).ok_or(OpError::ShapeMismatch)?;
+        // 输出通道数也要按组均分
+        make_eq(&[m, &(m.clone() / groups * groups)]).ok_or(OpError::ShapeMismatch)?;
+        let m = match b {
+            Some(b) => {
                 dims!([mb] = b);
+                make_eq(&[m, mb]).ok_or(OpError::ShapeMismatch)?
+            }
+            None => m.clone(),
+        };
 
-                // Check if channel match
-                if c != ck {
-                    return Err(OpError::ShapeMismatch);
-                }
+        let mut shape = vec![n.clone(), m];
+        for (i, (len, k)) in spatial.iter().zip(kernel).enumerate() {
+            shape.push(out_len(len, k, stride[i], padding[i], dilation[i]))
+        }
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
 
-                // Check if embd_dims match
-                if m != mb {
-                    return Err(OpError::ShapeMismatch);
-                }
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::ROW_MAJOR]
+    }
+}
 
-                let ny = n.clone();
-                let my = make_eq(&[m, mb]).ok_or(OpError::ShapeMismatch)?;
-                let hy = height.clone() / hk.clone();
-                let wy = width.clone() / wk.clone();
+/// 读取每个空间维度一个值的参数，缺省时都取 `default`。
+fn per_dim(
+    args: &HashMap<String, Arg>,
+    key: &str,
+    ndim: usize,
+    default: usize,
+) -> Result<Vec<usize>, OpError> {
+    match args.get(key) {
+        None => Ok(vec![default; ndim]),
+        Some(Arg::Arr(arr)) if arr.len() == ndim => arr
+            .iter()
+            .map(|arg| match arg {
+                &Arg::Int(val) => Ok(val as usize),
+                _ => Err(OpError::ArgError),
+            })
+            .collect(),
+        Some(_) => Err(OpError::ArgError),
+    }
+}
 
-                Ok(vec![TensorMeta::new(x.dt, [ny, my, hy, wy])])
-            }
-            _ => Err(OpError::ShapeError),
-        }
+/// 卷积在一个空间维度上的输出长度。
+fn out_len(len: &Dim, k: &Dim, stride: usize, padding: usize, dilation: usize) -> Dim {
+    (len.clone() + 2 * padding - (k.clone() - 1) * dilation - 1) / stride + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Conv;
+    use crate::{Arg, Dim, TensorMeta, digit_layout::types, op::Operator};
+    use std::collections::HashMap;
+
+    #[test]
+    fn out_len() {
+        // 高为变量，步长 2、补齐 1；宽为 7，膨胀 2
+        let x = TensorMeta::new(types::F32, [1.into(), 3.into(), Dim::from("h"), 7.into()]);
+        let w = TensorMeta::new(types::F32, [8, 3, 3, 3].map(Dim::from));
+        let arg = Arg::dict([
+            ("stride".into(), Arg::arr([Arg::int(2), Arg::int(1)])),
+            ("padding".into(), Arg::arr([Arg::int(1), Arg::int(0)])),
+            ("dilation".into(), Arg::arr([Arg::int(1), Arg::int(2)])),
+        ]);
+        let [y] = &*Conv.infer(&[x, w], Some(&arg)).unwrap() else {
+            panic!()
+        };
+
+        let shape = |h: usize| {
+            let value = HashMap::from([("h", h)]);
+            y.shape
+                .iter()
+                .map(|d| d.substitute(&value).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shape(224), [1, 8, 112, 3]);
+        assert_eq!(shape(5), [1, 8, 3, 3]);
+        assert_eq!(shape(1), [1, 8, 1, 3]);
     }
 }
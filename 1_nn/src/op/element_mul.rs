@@ -28,4 +28,8 @@ impl Operator for ElementMul {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0), (0, 1)]
+    }
 }
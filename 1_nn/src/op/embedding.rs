@@ -1,5 +1,5 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct Embedding;
@@ -29,4 +29,8 @@ impl Operator for Embedding {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
 }
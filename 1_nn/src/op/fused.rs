@@ -0,0 +1,114 @@
+//! 融合算子，形状推导由被融合的算子依次推导得到。
+
+use super::{
+    OpError, Operator, activation::SwiGLU, attention::Attention, linear::Linear, macros::*,
+    normalization::RmsNorm, rope::Rope, split::Split,
+};
+use crate::{Arg, LayoutReq, TensorMeta};
+
+/// `rms-norm` + `linear`，输入为 `[x, scale, w]` 或 `[x, scale, w, b]`，参数为 epsilon。
+pub struct RmsNormLinear;
+
+impl Operator for RmsNormLinear {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let epsilon = args.ok_or(OpError::ArgError)?;
+
+        match inputs {
+            [x, scale, linear @ ..] if matches!(linear.len(), 1 | 2) => {
+                destruct!([y] = RmsNorm.infer(&[x.clone(), scale.clone()], Some(epsilon))?);
+                Linear.infer(&[&[y], linear].concat(), Some(&Arg::Bool(false)))
+            }
+            _ => Err(OpError::ShapeError),
+        }
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
+}
+
+/// `linear` + `split` + `swiglu`，输入为 `[x, w]` 或 `[x, w, b]`，没有参数。
+pub struct LinearSwiGLU;
+
+impl Operator for LinearSwiGLU {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x] = Linear.infer(inputs, Some(&Arg::Bool(false)))?);
+        dims!([_, d] = x);
+        let d = d.clone() / 2;
+        let split = Arg::dict([
+            ("axis".into(), Arg::int(1)),
+            ("parts".into(), Arg::arr([d.clone(), d].map(Arg::from))),
+        ]);
+        let gate_up = Split.infer(&[x], Some(&split))?;
+        SwiGLU.infer(&gate_up, None)
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
+}
+
+/// `linear` + `split` + 两个 `rope`，输入为 `[x, w, pos, sin, cos]` 或 `[x, w, b, pos, sin, cos]`，
+/// 参数中的 `parts` 与 `split` 相同，输出为 `[q, k, v]`。
+pub struct QkvRope;
+
+impl Operator for QkvRope {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let Some(parts) = args.get("parts") else {
+            return Err(OpError::ArgError);
+        };
+
+        let (linear, rope) = match inputs.len() {
+            5 => inputs.split_at(2),
+            6 => inputs.split_at(3),
+            _ => return Err(OpError::ShapeError),
+        };
+
+        destruct!([qkv] = Linear.infer(linear, Some(&Arg::Bool(false)))?);
+        let split = Arg::dict([
+            ("axis".into(), Arg::int(1)),
+            ("parts".into(), parts.clone()),
+        ]);
+        destruct!([q, k, v] = Split.infer(&[qkv], Some(&split))?);
+        destruct!([q] = Rope.infer(&[&[q], rope].concat(), None)?);
+        destruct!([k] = Rope.infer(&[&[k], rope].concat(), None)?);
+
+        Ok(vec![q, k, v])
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
+}
+
+/// `attention` + 带残差的 `linear`，输入为 `[q, k, v, residual, w]` 或 `[q, k, v, residual, w, b]`，
+/// 参数与 `attention` 相同。
+pub struct AttentionOutput;
+
+impl Operator for AttentionOutput {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        match inputs {
+            [q, k, v, linear @ ..] if matches!(linear.len(), 2 | 3) => {
+                destruct!([o] = Attention.infer(&[q.clone(), k.clone(), v.clone()], args)?);
+                Linear.infer(&[&[o], linear].concat(), Some(&Arg::Bool(true)))
+            }
+            _ => Err(OpError::ShapeError),
+        }
+    }
+
+    /// 输出可以直接累加到残差上。
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 3)]
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST; 3]
+    }
+}
@@ -1,5 +1,5 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct Linear;
@@ -62,4 +62,16 @@ impl Operator for Linear {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    /// 带残差时输出可以直接累加到残差上。
+    fn inplace(&self, arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        match arg {
+            Some(Arg::Bool(true)) => vec![(0, 1)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
 }
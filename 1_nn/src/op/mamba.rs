@@ -1,15 +1,19 @@
-use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use super::{OpError, Operator, check_state, macros::*, split_state};
+use crate::{Arg, Dim, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct CausalConv1d;
 
 impl Operator for CausalConv1d {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let Some(Arg::Arr(_)) = args else {
+        let Some(Arg::Arr(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let Some(Arg::Int(padding)) = args.get(1) else {
             return Err(OpError::ArgError);
         };
 
+        let (inputs, state) = split_state(inputs, 3)?;
         destruct!([x, w, b] = inputs);
 
         dims!([l, d_in] = x);
@@ -19,7 +23,17 @@ impl Operator for CausalConv1d {
         make_eq(&[d_in, d_in2]).ok_or(OpError::ShapeMismatch)?;
         make_eq(&[d_in, d_in3]).ok_or(OpError::ShapeMismatch)?;
 
-        Ok(vec![TensorMeta::new(x.dt, [l.clone(), d_in.clone()])])
+        let mut outputs = vec![TensorMeta::new(x.dt, [l.clone(), d_in.clone()])];
+        // 状态为最近的 `padding` 个输入，输出的新状态形状相同
+        if let Some(state) = state {
+            check_state(state, x.dt, &[Dim::from(*padding as usize), d_in.clone()])?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::ROW_MAJOR]
     }
 }
 
@@ -31,6 +45,7 @@ impl Operator for SelectiveScan {
             return Err(OpError::ArgError);
         }
 
+        let (inputs, state) = split_state(inputs, 6)?;
         destruct!([x, delta, a, b, c, d] = inputs);
         dims!([l, d_in] = x);
         dims!([l2, d_in2] = delta);
@@ -48,6 +63,65 @@ impl Operator for SelectiveScan {
         make_eq(&[d_state, d_state2]).ok_or(OpError::ShapeMismatch)?;
         make_eq(&[d_state, d_state3]).ok_or(OpError::ShapeMismatch)?;
 
-        Ok(vec![TensorMeta::new(x.dt, [l.clone(), d_in.clone()])])
+        let mut outputs = vec![TensorMeta::new(x.dt, [l.clone(), d_in.clone()])];
+        // 状态为每个通道的隐状态，输出的新状态形状相同
+        if let Some(state) = state {
+            check_state(state, x.dt, &[d_in.clone(), d_state.clone()])?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::ROW_MAJOR; 2]
+    }
+}
+
+/// Mamba-2 的 SSD 扫描，参数为 B、C 的组数 `ng`，输入为 `[x, dt, a, b, c, d, dt_bias]`，
+/// 形状依次为 `[l, d_in]`、`[l, nh]`、`[nh]`、`[l, ng * d_state]`、`[l, ng * d_state]`、`[nh]`、`[nh]`。
+///
+/// `d_in` 均分为 `nh` 个头，每个头的 A、D 都是标量，`dt` 加上 `dt_bias` 后经过 softplus。
+/// A、D 和 `dt_bias` 是参数，数据类型可以与 `x` 不同。
+/// 可选状态为每个头的隐状态，形状为 `[nh, d_in / nh, d_state]`。
+pub struct SsdScan;
+
+impl Operator for SsdScan {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(&Arg::Int(ng)) = args else {
+            return Err(OpError::ArgError);
+        };
+        if ng == 0 {
+            return Err(OpError::ArgError);
+        }
+
+        let (inputs, state) = split_state(inputs, 7)?;
+        destruct!([x, dt, a, b, c, d, dt_bias] = inputs);
+        if [dt.dt, b.dt, c.dt].iter().any(|&dt| dt != x.dt) {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([l, d_in] = x);
+        dims!([l2, nh] = dt);
+        dims!([nh2] = a);
+        dims!([l3, bc] = b);
+        dims!([l4, bc2] = c);
+        dims!([nh3] = d);
+        dims!([nh4] = dt_bias);
+
+        let l = make_eq(&[l, l2, l3, l4]).ok_or(OpError::ShapeMismatch)?;
+        let nh = make_eq(&[nh, nh2, nh3, nh4]).ok_or(OpError::ShapeMismatch)?;
+        let bc = make_eq(&[bc, bc2]).ok_or(OpError::ShapeMismatch)?;
+
+        let mut outputs = vec![TensorMeta::new(x.dt, [l, d_in.clone()])];
+        // 状态为每个头的隐状态，输出的新状态形状相同
+        if let Some(state) = state {
+            let shape = [nh.clone(), d_in.clone() / nh, bc / ng as usize];
+            check_state(state, x.dt, &shape)?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::ROW_MAJOR; 2]
     }
 }
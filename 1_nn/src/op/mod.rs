@@ -1,4 +1,6 @@
-﻿use crate::{Arg, TensorMeta};
+﻿use crate::{Arg, Dim, LayoutReq, TensorMeta};
+use arg::make_eq;
+use tensor::digit_layout::DigitLayout;
 
 pub mod activation;
 pub mod add;
@@ -8,11 +10,15 @@ pub mod concat;
 pub mod conv;
 pub mod element_mul;
 pub mod embedding;
+pub mod fused;
 pub mod linear;
 pub mod mamba;
 pub mod merge;
+pub mod moe;
 pub mod mrope;
 pub mod normalization;
+pub mod offload;
+pub mod rearrange;
 pub mod rope;
 pub mod rwkv;
 pub mod split;
@@ -22,6 +28,19 @@ pub mod transpose;
 /// 计算图层算子，只考虑形状推导
 pub trait Operator {
     fn infer(&self, inputs: &[TensorMeta], arg: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError>;
+
+    /// 原地计算声明，每项 `(output, input)` 表示第 `output` 个输出可以复用第 `input` 个输入的存储，
+    /// 即算子逐元素读取该输入后才写入对应位置的输出。默认不能原地计算。
+    fn inplace(&self, arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        let _ = arg;
+        Vec::new()
+    }
+
+    /// 对每个输入的布局要求，缺少的输入接受任意布局。默认接受任意布局。
+    fn layout(&self, arg: Option<&Arg>) -> Vec<LayoutReq> {
+        let _ = arg;
+        Vec::new()
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -32,15 +51,49 @@ pub enum OpError {
     ShapeError,
     ShapeMismatch,
     ArgError,
+    /// 输入的数量与声明的不符，或缺少需要的输入。
+    ArityError,
+    /// 图变换破坏了图结构，例如重定向后形成环，或删除的节点的输出仍被使用。
+    TopoError,
+}
+
+/// 拆出附加在 `n` 个输入之后的可选状态输入。
+pub(crate) fn split_state(
+    inputs: &[TensorMeta],
+    n: usize,
+) -> Result<(&[TensorMeta], Option<&TensorMeta>), OpError> {
+    match inputs.len() {
+        len if len == n => Ok((inputs, None)),
+        len if len == n + 1 => Ok((&inputs[..n], inputs.last())),
+        _ => Err(OpError::ShapeError),
+    }
+}
+
+/// 检查状态的数据类型和形状。
+pub(crate) fn check_state(
+    state: &TensorMeta,
+    dt: DigitLayout,
+    shape: &[Dim],
+) -> Result<(), OpError> {
+    if state.dt != dt {
+        return Err(OpError::DataTypeMismatch);
+    }
+    if state.shape.len() != shape.len() {
+        return Err(OpError::ShapeError);
+    }
+    for (a, b) in state.shape.iter().zip(shape) {
+        make_eq(&[a, b]).ok_or(OpError::ShapeMismatch)?;
+    }
+    Ok(())
 }
 
 pub mod macros {
     macro_rules! destruct {
         ([$( $name:ident ),+] = $iter:expr) => {
             let mut iter = $iter.into_iter();
-            $( let $name = iter.next().ok_or(OpError::ShapeError)?; )+
+            $( let $name = iter.next().ok_or(OpError::ArityError)?; )+
             if iter.next().is_some() {
-                return Err(OpError::ShapeError);
+                return Err(OpError::ArityError);
             }
         };
     }
@@ -0,0 +1,96 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, LayoutReq, TensorMeta};
+use arg::make_eq;
+use tensor::digit_layout::types;
+
+/// 专家路由，参数为每个 token 选择的专家数 `k`，输入为形状为 `[n, n_expert]` 的路由打分，
+/// 输出形状都是 `[n, k]` 的选中专家序号和归一化的权重。
+pub struct MoeGate;
+
+impl Operator for MoeGate {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(&Arg::Int(k)) = args else {
+            return Err(OpError::ArgError);
+        };
+        if k == 0 {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([logits] = inputs);
+        dims!([n, _n_expert] = logits);
+
+        let shape = [n.clone(), Dim::from(k as usize)];
+        Ok(vec![
+            TensorMeta::new(types::U32, shape.clone()),
+            TensorMeta::new(logits.dt, shape),
+        ])
+    }
+}
+
+/// 专家线性层，输入为 `[x, routes, w]` 或 `[x, routes, w, b]`，
+/// `routes` 是 [`MoeGate`] 输出的 `[n, k]` 专家序号，`w` 和 `b` 是所有专家堆叠的形状为
+/// `[n_expert, m, d]` 的权重和 `[n_expert, m]` 的偏置。
+/// `x` 的形状为 `[n, d]` 时每个 token 分别与选中的 `k` 个专家相乘，为 `[n, k, d]` 时第 `j`
+/// 项只与第 `j` 个选中的专家相乘，输出形状都是 `[n, k, m]`，只计算被选中的专家。
+pub struct MoeLinear;
+
+impl Operator for MoeLinear {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        let (x, routes, w, b) = match inputs {
+            [x, routes, w] => (x, routes, w, None),
+            [x, routes, w, b] => (x, routes, w, Some(b)),
+            _ => return Err(OpError::ShapeError),
+        };
+        if routes.dt != types::U32 {
+            return Err(OpError::DataTypeError);
+        }
+        dims!([n_routes, k] = routes);
+        dims!([_n_expert, m, d_w] = w);
+        let (n_x, d_x) = match x.shape() {
+            [n, d] => (n, d),
+            [n, k_x, d] => {
+                make_eq(&[k, k_x]).ok_or(OpError::ShapeMismatch)?;
+                (n, d)
+            }
+            _ => return Err(OpError::ShapeError),
+        };
+        make_eq(&[d_x, d_w]).ok_or(OpError::ShapeMismatch)?;
+        let m = match b {
+            Some(b) => {
+                dims!([_n_expert_b, m_b] = b);
+                make_eq(&[_n_expert, _n_expert_b]).ok_or(OpError::ShapeMismatch)?;
+                make_eq(&[m, m_b]).ok_or(OpError::ShapeMismatch)?
+            }
+            None => m.clone(),
+        };
+        let n = make_eq(&[n_x, n_routes]).ok_or(OpError::ShapeMismatch)?;
+        Ok(vec![TensorMeta::new(x.dt, [n, k.clone(), m])])
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
+}
+
+/// 专家合并，输入为 `[weights, y]`，`weights` 是 [`MoeGate`] 输出的 `[n, k]` 权重，
+/// `y` 是 [`MoeLinear`] 输出的 `[n, k, d]` 选中专家的输出，每个 token 输出选中专家的加权和。
+pub struct MoeCombine;
+
+impl Operator for MoeCombine {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([weights, y] = inputs);
+        dims!([n_weights, k_weights] = weights);
+        dims!([n_y, k_y, d] = y);
+        let n = make_eq(&[n_weights, n_y]).ok_or(OpError::ShapeMismatch)?;
+        make_eq(&[k_weights, k_y]).ok_or(OpError::ShapeMismatch)?;
+        Ok(vec![TensorMeta::new(y.dt, [n, d.clone()])])
+    }
+}
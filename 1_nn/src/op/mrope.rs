@@ -1,5 +1,5 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct Mrope;
@@ -34,4 +34,8 @@ impl Operator for Mrope {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
 }
@@ -1,5 +1,5 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct RmsNorm;
@@ -33,6 +33,10 @@ impl Operator for RmsNorm {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
 }
 
 pub struct LayerNorm;
@@ -55,4 +59,75 @@ impl Operator for LayerNorm {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
+}
+
+/// 分组归一化，输入为 `[x, scale, bias]`，参数为 `{epsilon, groups}`，`x` 的最后一维均分为 `groups` 组分别归一化。
+pub struct GroupNorm;
+
+impl Operator for GroupNorm {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let (Some(Arg::Float(_)), Some(Arg::Int(groups))) =
+            (args.get("epsilon"), args.get("groups"))
+        else {
+            return Err(OpError::ArgError);
+        };
+        if *groups == 0 {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x, scale, bias] = inputs);
+        dims!([n, d] = x);
+        dims!([d_scale] = scale);
+        dims!([d_bias] = bias);
+
+        let d = make_eq(&[d, d_scale, d_bias]).ok_or(OpError::ShapeMismatch)?;
+        Ok(vec![TensorMeta::new(x.dt, [n.clone(), d])])
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
+}
+
+/// 门控的分组 RMS 归一化，输入为 `[x, z, scale]`，参数为 `{epsilon, groups}`，
+/// 计算 `x * silu(z)` 后将最后一维均分为 `groups` 组分别归一化，再乘以 `scale`。
+pub struct GatedRmsNorm;
+
+impl Operator for GatedRmsNorm {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let (Some(Arg::Float(_)), Some(Arg::Int(groups))) =
+            (args.get("epsilon"), args.get("groups"))
+        else {
+            return Err(OpError::ArgError);
+        };
+        if *groups == 0 {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x, z, scale] = inputs);
+        if z.dt != x.dt {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([n, d] = x);
+        dims!([n_z, d_z] = z);
+        dims!([d_scale] = scale);
+
+        let n = make_eq(&[n, n_z]).ok_or(OpError::ShapeMismatch)?;
+        let d = make_eq(&[d, d_z, d_scale]).ok_or(OpError::ShapeMismatch)?;
+        Ok(vec![TensorMeta::new(x.dt, [n, d])])
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST; 2]
+    }
 }
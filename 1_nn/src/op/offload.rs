@@ -0,0 +1,35 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, TensorMeta};
+
+/// 把块复制到主机上形状相同的张量，用于卸载暂时不用的块。
+pub struct CopyOut;
+
+impl Operator for CopyOut {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x] = inputs);
+
+        Ok(vec![x.clone()])
+    }
+}
+
+/// 把卸载到主机的块复制回来，参数为输出数，每个输出对应之后使用的一种布局。
+pub struct CopyIn;
+
+impl Operator for CopyIn {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(&Arg::Int(n)) = args else {
+            return Err(OpError::ArgError);
+        };
+        if n == 0 {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x] = inputs);
+
+        Ok(vec![x.clone(); n as usize])
+    }
+}
@@ -0,0 +1,17 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, TensorMeta};
+
+/// 把输入复制为形状相同的连续张量。
+pub struct Rearrange;
+
+impl Operator for Rearrange {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x] = inputs);
+
+        Ok(vec![x.clone()])
+    }
+}
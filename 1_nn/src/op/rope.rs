@@ -1,5 +1,5 @@
 use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use crate::{Arg, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct Rope;
@@ -34,4 +34,12 @@ impl Operator for Rope {
             _ => Err(OpError::ShapeError),
         }
     }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST]
+    }
 }
@@ -1,5 +1,5 @@
-use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use super::{OpError, Operator, check_state, macros::*, split_state};
+use crate::{Arg, Dim, LayoutReq, TensorMeta};
 use arg::make_eq;
 
 pub struct RWKVTimeMix;
@@ -10,6 +10,7 @@ impl Operator for RWKVTimeMix {
             return Err(OpError::ArgError);
         };
 
+        let (inputs, state) = split_state(inputs, 7)?;
         match inputs {
             [x, k, v, r, time_mix_k, time_mix_v, time_mix_r] => {
                 // 检查所有输入的形状一致性
@@ -80,7 +81,16 @@ impl Operator for RWKVTimeMix {
                 }
 
                 // 输出形状与输入x相同
-                Ok(vec![TensorMeta::new(x.dt, [batch, seq_len, d_model])])
+                let mut outputs = vec![TensorMeta::new(
+                    x.dt,
+                    [batch.clone(), seq_len, d_model.clone()],
+                )];
+                // 状态为每个序列的 token shift 以及 wkv 的 a、b、p，输出的新状态形状相同
+                if let Some(state) = state {
+                    check_state(state, x.dt, &[batch, Dim::from(4), d_model])?;
+                    outputs.push(state.clone())
+                }
+                Ok(outputs)
             }
             _ => Err(OpError::ShapeError),
         }
@@ -95,6 +105,7 @@ impl Operator for RWKVChannelMix {
             return Err(OpError::ArgError);
         };
 
+        let (inputs, state) = split_state(inputs, 5)?;
         match inputs {
             [x, k, r, time_mix_k, time_mix_r] => {
                 // 检查所有输入的形状一致性
@@ -127,7 +138,7 @@ impl Operator for RWKVChannelMix {
                 ])
                 .ok_or(OpError::ShapeMismatch)?;
 
-                let _d_model = make_eq(&[
+                let d_model = make_eq(&[
                     &x.shape[2],
                     d_model,
                     &time_mix_k.shape[2],
@@ -147,12 +158,202 @@ impl Operator for RWKVChannelMix {
                 }
 
                 // 输出形状与k相同（因为这是channel mix的中间结果）
-                Ok(vec![TensorMeta::new(
+                let mut outputs = vec![TensorMeta::new(
                     x.dt,
-                    [batch, seq_len, d_model_k.clone()],
-                )])
+                    [batch.clone(), seq_len, d_model_k.clone()],
+                )];
+                // 状态为每个序列的 token shift，输出的新状态形状相同
+                if let Some(state) = state {
+                    check_state(state, x.dt, &[batch, d_model])?;
+                    outputs.push(state.clone())
+                }
+                Ok(outputs)
             }
             _ => Err(OpError::ShapeError),
         }
     }
 }
+
+/// 检查张量的数据类型相同且形状都是 `[n, d]`，返回 `n` 和 `d`。
+fn tokens(tensors: &[&TensorMeta]) -> Result<(Dim, Dim), OpError> {
+    let dt = tensors[0].dt;
+    let mut n = Vec::with_capacity(tensors.len());
+    let mut d = Vec::with_capacity(tensors.len());
+    for t in tensors {
+        if t.dt != dt {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([n_, d_] = t);
+        n.push(n_);
+        d.push(d_);
+    }
+    Ok((
+        make_eq(&n).ok_or(OpError::ShapeMismatch)?,
+        make_eq(&d).ok_or(OpError::ShapeMismatch)?,
+    ))
+}
+
+/// token shift 插值，参数为插值的组数 `m`，输入为 `[x, mu_0, .., mu_{m-1}]`，
+/// `x` 形状为 `[n, d]`，`mu_i` 形状为 `[1, d]`，依次输出 `x + (shift(x) - x) * mu_i`。
+///
+/// 可选状态为上一次调用的最后一个 token，形状为 `[1, d]`。
+pub struct RWKVLerp;
+
+impl Operator for RWKVLerp {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(&Arg::Int(m)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let m = m as usize;
+        if m == 0 {
+            return Err(OpError::ArgError);
+        }
+
+        let (inputs, state) = split_state(inputs, m + 1)?;
+        let [x, mus @ ..] = inputs else {
+            return Err(OpError::ShapeError);
+        };
+        dims!([n, d] = x);
+        for mu in mus {
+            check_state(mu, x.dt, &[Dim::from(1), d.clone()])?
+        }
+
+        let mut outputs = vec![TensorMeta::new(x.dt, [n.clone(), d.clone()]); m];
+        if let Some(state) = state {
+            check_state(state, x.dt, &[Dim::from(1), d.clone()])?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+}
+
+/// RWKV-6 的数据相关 token shift，输入为 `[x, maa_x, w1, w2, maa]`，
+/// 形状依次为 `[n, d]`、`[1, d]`、`[5r, d]`、`[5, d, r]`、`[5, d]`，依次输出 w、k、v、r、g 的插值结果。
+///
+/// 可选状态为上一次调用的最后一个 token，形状为 `[1, d]`。
+pub struct RWKV6DdLerp;
+
+impl Operator for RWKV6DdLerp {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        let (inputs, state) = split_state(inputs, 5)?;
+        destruct!([x, maa_x, w1, w2, maa] = inputs);
+        dims!([n, d] = x);
+        check_state(maa_x, x.dt, &[Dim::from(1), d.clone()])?;
+
+        if [w1.dt, w2.dt, maa.dt].iter().any(|&dt| dt != x.dt) {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([r5, d_w1] = w1);
+        dims!([five, d_w2, r] = w2);
+        dims!([five_maa, d_maa] = maa);
+        let d = make_eq(&[d, d_w1, d_w2, d_maa]).ok_or(OpError::ShapeMismatch)?;
+        make_eq(&[five, five_maa, &Dim::from(5)]).ok_or(OpError::ShapeMismatch)?;
+        make_eq(&[r5, &(r.clone() * 5)]).ok_or(OpError::ShapeMismatch)?;
+
+        let mut outputs = vec![TensorMeta::new(x.dt, [n.clone(), d.clone()]); 5];
+        if let Some(state) = state {
+            check_state(state, x.dt, &[Dim::from(1), d])?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+}
+
+/// RWKV-6 的多头 wkv，输入为 `[r, k, v, w, u]`，`u` 形状为 `[nh, hs]`，其余形状为 `[n, d]`，`d = nh * hs`。
+///
+/// `w` 为未经变换的衰减，每步的衰减系数为 `exp(-exp(w))`。
+/// 可选状态为每个头的 wkv 矩阵，形状为 `[nh, hs, hs]`。
+pub struct RWKV6Wkv;
+
+impl Operator for RWKV6Wkv {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        let (inputs, state) = split_state(inputs, 5)?;
+        destruct!([r, k, v, w, u] = inputs);
+        let (n, d) = tokens(&[r, k, v, w])?;
+        if u.dt != r.dt {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([nh, hs] = u);
+        let d = make_eq(&[&d, &(nh.clone() * hs.clone())]).ok_or(OpError::ShapeMismatch)?;
+
+        let mut outputs = vec![TensorMeta::new(r.dt, [n, d])];
+        if let Some(state) = state {
+            check_state(state, r.dt, &[nh.clone(), hs.clone(), hs.clone()])?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST; 4]
+    }
+}
+
+/// RWKV-7 的 value 残差，输入为形状都是 `[n, d]` 的 `[v, v_first, gate]`，
+/// 输出 `v + (v_first - v) * sigmoid(gate)`。
+pub struct RWKV7VMix;
+
+impl Operator for RWKV7VMix {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([v, v_first, gate] = inputs);
+        let (n, d) = tokens(&[v, v_first, gate])?;
+        Ok(vec![TensorMeta::new(v.dt, [n, d])])
+    }
+
+    fn inplace(&self, _arg: Option<&Arg>) -> Vec<(usize, usize)> {
+        vec![(0, 0)]
+    }
+}
+
+/// RWKV-7 的时间混合，参数为组归一化的 epsilon，输入为 `[r, w, k, v, a, k_k, k_a, r_k, ln_scale, ln_bias]`，
+/// `r`、`w`、`k`、`v`、`a` 形状为 `[n, d]`，`r_k` 形状为 `[nh, hs]`，其余形状为 `[d]`。
+///
+/// 算子完成 k 的归一化和调整、wkv7 递推、按头的组归一化以及 bonus 项，
+/// 其中 `w` 按 `exp(-0.606531 * sigmoid(w))` 变换，`a` 按 sigmoid 变换。
+/// 可选状态为每个头的 wkv 矩阵，形状为 `[nh, hs, hs]`。
+pub struct RWKV7TimeMix;
+
+impl Operator for RWKV7TimeMix {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Float(_epsilon)) = args else {
+            return Err(OpError::ArgError);
+        };
+
+        let (inputs, state) = split_state(inputs, 10)?;
+        destruct!([r, w, k, v, a, k_k, k_a, r_k, ln_scale, ln_bias] = inputs);
+        let (n, d) = tokens(&[r, w, k, v, a])?;
+        if [k_k.dt, k_a.dt, r_k.dt].iter().any(|&dt| dt != r.dt) {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([d_kk] = k_k);
+        dims!([d_ka] = k_a);
+        dims!([d_scale] = ln_scale);
+        dims!([d_bias] = ln_bias);
+        dims!([nh, hs] = r_k);
+        let d = make_eq(&[&d, d_kk, d_ka, d_scale, d_bias, &(nh.clone() * hs.clone())])
+            .ok_or(OpError::ShapeMismatch)?;
+
+        let mut outputs = vec![TensorMeta::new(r.dt, [n, d])];
+        if let Some(state) = state {
+            check_state(state, r.dt, &[nh.clone(), hs.clone(), hs.clone()])?;
+            outputs.push(state.clone())
+        }
+        Ok(outputs)
+    }
+
+    fn layout(&self, _arg: Option<&Arg>) -> Vec<LayoutReq> {
+        vec![LayoutReq::CONTIGUOUS_LAST; 5]
+    }
+}
@@ -26,6 +26,9 @@ impl Operator for Split {
                 }
             })
             .collect::<Result<Vec<_>, _>>()?;
+        if parts.is_empty() || parts.contains(&Dim::from(0)) {
+            return Err(OpError::ArgError);
+        }
 
         destruct!([x] = inputs);
 
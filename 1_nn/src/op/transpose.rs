@@ -30,6 +30,12 @@ impl Operator for Transpose {
         if perm.len() != shape.len() {
             return Err(OpError::ShapeError);
         }
+        // perm 必须是 0..ndim 的排列
+        let mut sorted = perm.clone();
+        sorted.sort_unstable();
+        if sorted.into_iter().ne(0..shape.len()) {
+            return Err(OpError::ArgError);
+        }
 
         let new_shape = perm.iter().map(|&p| shape[p].clone()).collect::<Vec<_>>();
 
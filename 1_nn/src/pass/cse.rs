@@ -0,0 +1,136 @@
+use super::{Pass, Rewriter};
+use crate::NNError;
+use std::{collections::HashMap, iter::zip};
+
+/// 公共子表达式消除。
+///
+/// - 名字和形状都相同的外部张量合并为一条边，保留序号最小的边；
+/// - 算子、参数和输入都相同的节点合并为一个，保留拓扑序中靠前的节点。
+///   输出绑定到外部张量的节点不参与合并，既不会被删除，也不会被其他节点替代。
+///
+/// 被合并的边和节点不再被使用，可以随后执行 [`Dce`](super::Dce) 清理。
+/// 返回合并的边数与节点数之和。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Cse;
+
+impl<T> Pass<T> for Cse {
+    fn run(&self, rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+        Ok(merge_externals(rw)? + merge_nodes(rw)?)
+    }
+}
+
+fn merge_externals<T>(rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+    let mut by_name = HashMap::new();
+    let mut merge = Vec::new();
+    for e in 0..rw.n_edge() {
+        let edge = rw.edge(e);
+        let Some(external) = &edge.external else {
+            continue;
+        };
+        // 有生产者的是绑定到外部张量的输出，不能合并
+        if rw.producer(e).is_some() || (rw.consumers(e).is_empty() && !rw.is_global_output(e)) {
+            continue;
+        }
+        let first = *by_name.entry(&external.name).or_insert(e);
+        // 同名的张量以不同形状加载时不能合并
+        if first != e && rw.edge(first).meta == edge.meta {
+            merge.push((e, first))
+        }
+    }
+
+    for &(old, new) in &merge {
+        rw.rewire(old, new)?
+    }
+    Ok(merge.len())
+}
+
+fn merge_nodes<T>(rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+    let mut seen = HashMap::<_, Vec<usize>>::new();
+    let mut n = 0;
+    // 按拓扑序处理，前驱合并后后继的输入才会相同
+    for i in rw.topo_order()? {
+        let node = rw.node(i).unwrap();
+        if node.outputs.iter().any(|&e| rw.edge(e).external.is_some()) {
+            continue;
+        }
+        let key = (node.node.value.name.clone(), node.inputs.clone());
+        let candidates = seen.entry(key).or_default();
+        let same = candidates.iter().copied().find(|&j| {
+            let other = rw.node(j).unwrap();
+            other.node.value.arg == node.node.value.arg && other.outputs.len() == node.outputs.len()
+        });
+        match same {
+            Some(j) => {
+                let outputs = rw.remove(i).unwrap().outputs;
+                let kept = rw.node(j).unwrap().outputs.clone();
+                for (old, new) in zip(outputs, kept) {
+                    rw.rewire(old, new)?
+                }
+                n += 1
+            }
+            None => candidates.push(i),
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cse;
+    use crate::{
+        Dim, Edge, External, GraphBuilder, NNGraph, Named, OpInfo, TensorMeta,
+        digit_layout::types,
+        op,
+        pass::{Dce, Pass},
+    };
+    use graph::{TopoBuilder, TopoInput::*};
+
+    #[test]
+    fn merge_and_prune() {
+        // a = x + w0, b = x + w1, y = a + b, z = x + x 未被使用，w0 和 w1 同名
+        let mut topo = TopoBuilder::new(1);
+        let a = topo.push([Edge(0), Local], 1).unwrap().start;
+        let b = topo.push([Edge(0), Local], 1).unwrap().start;
+        let y = topo.push([Edge(a), Edge(b)], 1).unwrap().start;
+        topo.push([Edge(0), Edge(0)], 1).unwrap();
+        let topo = topo.build([y]).unwrap();
+
+        let meta = TensorMeta::new(types::F32, [Dim::from("n"), Dim::from(4)]);
+        let edges = (0..topo.n_edge())
+            .map(|e| Edge {
+                meta: meta.clone(),
+                external: matches!(e, 1 | 3).then(|| External {
+                    name: "w".into(),
+                    item: e,
+                }),
+            })
+            .collect();
+        let nodes = ["a", "b", "y", "z"]
+            .map(|name| Named {
+                name: name.into(),
+                value: OpInfo {
+                    name: "add".into(),
+                    arg: None,
+                },
+            })
+            .into();
+        let graph = NNGraph {
+            graph: graph::Graph { topo, nodes, edges },
+            bodies: Box::new([]),
+        };
+
+        let mut builder = GraphBuilder::default();
+        builder.register_op("add", op::add::Add);
+        let pm = builder.pass_manager();
+        let mut rw = pm.rewriter(graph);
+        // 合并 1 条外部边和 1 个节点
+        assert_eq!(Cse.run(&mut rw).unwrap(), 2);
+        assert_eq!(Dce.run(&mut rw).unwrap(), 1);
+
+        let graph = rw.into_graph().unwrap().graph;
+        let names = graph.nodes.iter().map(|n| &*n.name).collect::<Vec<_>>();
+        assert_eq!(names, ["a", "y"]);
+        let y = graph.topo.iter().nth(1).unwrap();
+        assert_eq!(y.inputs[0], y.inputs[1]);
+    }
+}
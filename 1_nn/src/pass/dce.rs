@@ -0,0 +1,46 @@
+use super::{Pass, Rewriter};
+use crate::NNError;
+
+/// 死代码消除。
+///
+/// 删除所有输出都不能到达全图输出或绑定的外部张量的节点，返回删除的节点数。
+///
+/// 输出绑定到外部张量的节点（例如循环状态的更新）总是保留。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Dce;
+
+impl<T> Pass<T> for Dce {
+    fn run(&self, rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+        // 从全图输出和绑定的外部张量出发，沿生产者反向标记活跃节点
+        let mut live = vec![false; rw.n_node()];
+        let mut stack = rw
+            .global_outputs()
+            .iter()
+            .copied()
+            .chain((0..rw.n_edge()).filter(|&e| rw.edge(e).external.is_some()))
+            .filter_map(|e| rw.producer(e))
+            .collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            if std::mem::replace(&mut live[i], true) {
+                continue;
+            }
+            stack.extend(
+                rw.node(i)
+                    .unwrap()
+                    .inputs
+                    .iter()
+                    .filter_map(|&e| rw.producer(e)),
+            )
+        }
+
+        let dead = rw
+            .nodes()
+            .filter(|&(i, _)| !live[i])
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        for &i in &dead {
+            rw.remove(i);
+        }
+        Ok(dead.len())
+    }
+}
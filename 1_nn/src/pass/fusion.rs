@@ -0,0 +1,166 @@
+use super::{
+    Pass, Pattern,
+    PatternInput::{self, Any, Output},
+    PatternNode, Rewriter,
+};
+use crate::{Arg, NNError};
+
+/// 算子融合。
+///
+/// 将 `TransformerBlk` 生成的未融合子图替换为融合算子，需要在构造器中注册对应的形状推导：
+///
+/// | 融合算子           | 被融合的子图                           | 形状推导                            |
+/// |:------------------:|:--------------------------------------:|:-----------------------------------:|
+/// | `attention-output` | `attention` → 带残差的 `linear`        | [`AttentionOutput`]                 |
+/// | `qkv-rope`         | `linear` → `split` → `rope` ×2         | [`QkvRope`]                         |
+/// | `linear-swiglu`    | `linear` → `split` → `swiglu`          | [`LinearSwiGLU`]                    |
+/// | `rms-norm-linear`  | `rms-norm` → `linear`                  | [`RmsNormLinear`]                   |
+///
+/// 融合按表中的顺序进行，已经被融合的 `linear` 不会再参与之后的融合。
+///
+/// [`AttentionOutput`]: crate::op::fused::AttentionOutput
+/// [`QkvRope`]: crate::op::fused::QkvRope
+/// [`LinearSwiGLU`]: crate::op::fused::LinearSwiGLU
+/// [`RmsNormLinear`]: crate::op::fused::RmsNormLinear
+#[derive(Clone, Copy, Debug)]
+pub struct Fusion {
+    pub attention_output: bool,
+    pub qkv_rope: bool,
+    pub linear_swiglu: bool,
+    pub rms_norm_linear: bool,
+}
+
+impl Fusion {
+    pub const ALL: Self = Self {
+        attention_output: true,
+        qkv_rope: true,
+        linear_swiglu: true,
+        rms_norm_linear: true,
+    };
+}
+
+impl<T> Pass<T> for Fusion {
+    fn run(&self, rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+        let mut n = 0;
+        if self.attention_output {
+            n += attention_output(rw)?
+        }
+        if self.qkv_rope {
+            n += qkv_rope(rw)?
+        }
+        if self.linear_swiglu {
+            n += linear_swiglu(rw)?
+        }
+        if self.rms_norm_linear {
+            n += rms_norm_linear(rw)?
+        }
+        Ok(n)
+    }
+}
+
+fn attention_output<T>(rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+    let mut n = 0;
+    for bias in [false, true] {
+        let mut pattern = Pattern::default();
+        let attn = pattern.push(PatternNode::new("attention", [Any, Any, Any]));
+        pattern.push(linear(Output(attn, 0), true, bias));
+
+        for m in pattern.find(rw) {
+            let arg = rw.node(m.nodes[attn]).unwrap().node.value.arg.clone();
+            rw.replace(&m, "", "attention-output", arg, m.inputs.iter().copied())?;
+            n += 1
+        }
+    }
+    Ok(n)
+}
+
+fn qkv_rope<T>(rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+    let mut n = 0;
+    for bias in [false, true] {
+        let mut pattern = Pattern::default();
+        let qkv = pattern.push(linear(Any, false, bias));
+        let split = pattern.push(split(Output(qkv, 0), 3));
+        let q = pattern.push(PatternNode::new("rope", [Output(split, 0), Any, Any, Any]));
+        let k = pattern.push(PatternNode::new("rope", [Output(split, 1), Any, Any, Any]));
+        pattern.expose(q, 0).expose(k, 0).expose(split, 2);
+
+        for m in pattern.find(rw) {
+            let (linear, rope) = m.inputs.split_at(if bias { 3 } else { 2 });
+            let (rope_q, rope_k) = rope.split_at(3);
+            // q 和 k 必须使用相同的位置和 sin cos 表
+            if rope_q != rope_k {
+                continue;
+            }
+            let name = rw.node(m.nodes[qkv]).unwrap().node.name.clone();
+            let Some(Arg::Dict(arg)) = &rw.node(m.nodes[split]).unwrap().node.value.arg else {
+                unreachable!()
+            };
+            let arg = Arg::dict([("parts".into(), arg["parts"].clone())]);
+            let inputs = [linear, rope_q].concat();
+            rw.replace(&m, name, "qkv-rope", Some(arg), inputs)?;
+            n += 1
+        }
+    }
+    Ok(n)
+}
+
+fn linear_swiglu<T>(rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+    let mut n = 0;
+    for bias in [false, true] {
+        let mut pattern = Pattern::default();
+        let up = pattern.push(linear(Any, false, bias));
+        let split = pattern.push(
+            split(Output(up, 0), 2)
+                .when(|_, _, outputs| matches!(outputs, [gate, up] if gate.shape == up.shape)),
+        );
+        pattern.push(PatternNode::new(
+            "swiglu",
+            [Output(split, 0), Output(split, 1)],
+        ));
+
+        for m in pattern.find(rw) {
+            let name = rw.node(m.nodes[up]).unwrap().node.name.clone();
+            rw.replace(&m, name, "linear-swiglu", None, m.inputs.iter().copied())?;
+            n += 1
+        }
+    }
+    Ok(n)
+}
+
+fn rms_norm_linear<T>(rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+    let mut n = 0;
+    for bias in [false, true] {
+        let mut pattern = Pattern::default();
+        let norm = pattern.push(PatternNode::new("rms-norm", [Any, Any]));
+        pattern.push(linear(Output(norm, 0), false, bias));
+
+        for m in pattern.find(rw) {
+            let epsilon = rw.node(m.nodes[norm]).unwrap().node.value.arg.clone();
+            rw.replace(&m, "", "rms-norm-linear", epsilon, m.inputs.iter().copied())?;
+            n += 1
+        }
+    }
+    Ok(n)
+}
+
+/// `linear` 的模式节点，带残差时输入为 `[x, residual, w(, b)]`，否则为 `[x, w(, b)]`。
+fn linear(x: PatternInput, residual: bool, bias: bool) -> PatternNode {
+    let mut inputs = vec![x];
+    if residual {
+        inputs.push(Any)
+    }
+    inputs.push(Any);
+    if bias {
+        inputs.push(Any)
+    }
+    PatternNode::new("linear", inputs)
+        .when(move |op, _, _| matches!(op.arg, Some(Arg::Bool(r)) if r == residual))
+}
+
+/// 沿第 1 维切分为 `n` 份的 `split` 模式节点。
+fn split(x: PatternInput, n: usize) -> PatternNode {
+    PatternNode::new("split", [x]).when(move |op, _, outputs| {
+        outputs.len() == n
+            && matches!(&op.arg, Some(Arg::Dict(arg)) if matches!(arg.get("axis"), Some(Arg::Int(1))))
+    })
+}
@@ -0,0 +1,67 @@
+//! 逻辑图变换。
+
+mod cse;
+mod dce;
+mod fusion;
+mod pattern;
+mod rewriter;
+
+use crate::{GraphBuilder, NNError, NNGraph, OpLib};
+use std::rc::Rc;
+
+pub use cse::Cse;
+pub use dce::Dce;
+pub use fusion::Fusion;
+pub use pattern::{Condition, Match, Pattern, PatternInput, PatternNode};
+pub use rewriter::{EditNode, Rewriter};
+
+/// 图变换遍。
+pub trait Pass<T> {
+    /// 在可编辑图上执行变换，返回改写的次数。
+    fn run(&self, rw: &mut Rewriter<T>) -> Result<usize, NNError>;
+}
+
+impl<T, F> Pass<T> for F
+where
+    F: Fn(&mut Rewriter<T>) -> Result<usize, NNError>,
+{
+    fn run(&self, rw: &mut Rewriter<T>) -> Result<usize, NNError> {
+        self(rw)
+    }
+}
+
+/// 按顺序执行一组图变换遍。
+pub struct PassManager<T> {
+    op_lib: Rc<OpLib>,
+    passes: Vec<Box<dyn Pass<T>>>,
+}
+
+impl GraphBuilder {
+    /// 创建使用此构造器中注册的算子进行形状推导的变换管理器。
+    pub fn pass_manager<T>(&self) -> PassManager<T> {
+        PassManager {
+            op_lib: self.op_lib(),
+            passes: Vec::new(),
+        }
+    }
+}
+
+impl<T> PassManager<T> {
+    pub fn add(&mut self, pass: impl Pass<T> + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn run(&self, graph: NNGraph<T>) -> Result<NNGraph<T>, NNError> {
+        let mut rw = self.rewriter(graph);
+        for pass in &self.passes {
+            pass.run(&mut rw)?;
+        }
+        rw.into_graph()
+    }
+
+    /// 将图转换为可编辑形式，用于手动执行变换。
+    pub fn rewriter(&self, graph: NNGraph<T>) -> Rewriter<T> {
+        Rewriter::new(graph, self.op_lib.clone())
+    }
+}
@@ -0,0 +1,318 @@
+use super::Rewriter;
+use crate::{OpInfo, TensorMeta};
+use std::{iter::zip, rc::Rc};
+
+/// 子图模式。
+///
+/// 模式节点按拓扑序添加，最后添加的节点是模式的根，其他节点都必须经由输入输出连接到根。
+#[derive(Clone, Default)]
+pub struct Pattern {
+    nodes: Vec<PatternNode>,
+    outputs: Vec<(usize, usize)>,
+}
+
+/// 模式节点，按算子名字、输入连接以及可选的条件匹配图中的节点。
+#[derive(Clone)]
+pub struct PatternNode {
+    op: String,
+    inputs: Box<[PatternInput]>,
+    cond: Option<Rc<Condition>>,
+}
+
+/// 匹配条件，参数为算子及其输入输出张量的元信息。
+pub type Condition = dyn Fn(&OpInfo, &[&TensorMeta], &[&TensorMeta]) -> bool;
+
+/// 模式节点的输入。
+#[derive(Clone, Copy, Debug)]
+pub enum PatternInput {
+    /// 任意边，匹配后记录到 [`Match::inputs`]。
+    Any,
+    /// 另一个模式节点的第几个输出。
+    Output(usize, usize),
+}
+
+/// 模式的一次匹配。
+#[derive(Clone, Debug)]
+pub struct Match {
+    /// 每个模式节点对应的图节点。
+    pub nodes: Box<[usize]>,
+    /// 按模式节点和输入的顺序排列的 [`PatternInput::Any`] 输入边。
+    pub inputs: Box<[usize]>,
+    /// 子图暴露的输出边，默认为根节点的所有输出。
+    pub outputs: Box<[usize]>,
+}
+
+impl PatternNode {
+    pub fn new(op: impl ToString, inputs: impl IntoIterator<Item = PatternInput>) -> Self {
+        Self {
+            op: op.to_string(),
+            inputs: inputs.into_iter().collect(),
+            cond: None,
+        }
+    }
+
+    /// 为模式节点附加匹配条件，多次附加的条件需要同时满足。
+    pub fn when(
+        mut self,
+        cond: impl Fn(&OpInfo, &[&TensorMeta], &[&TensorMeta]) -> bool + 'static,
+    ) -> Self {
+        self.cond = Some(match self.cond.take() {
+            Some(prev) => Rc::new(move |op, inputs, outputs| {
+                prev(op, inputs, outputs) && cond(op, inputs, outputs)
+            }),
+            None => Rc::new(cond),
+        });
+        self
+    }
+}
+
+impl Pattern {
+    /// 添加一个模式节点，返回其序号。
+    pub fn push(&mut self, node: PatternNode) -> usize {
+        let i = self.nodes.len();
+        for input in &node.inputs {
+            if let &PatternInput::Output(j, _) = input {
+                assert!(j < i, "pattern nodes must be added in topological order")
+            }
+        }
+        self.nodes.push(node);
+        i
+    }
+
+    /// 将模式节点的第 `output` 个输出暴露为子图输出。
+    ///
+    /// 一旦暴露了任何输出，根节点的输出也需要显式暴露。
+    pub fn expose(&mut self, node: usize, output: usize) -> &mut Self {
+        assert!(node < self.nodes.len());
+        self.outputs.push((node, output));
+        self
+    }
+
+    /// 在图中查找互不重叠的匹配。
+    ///
+    /// 未暴露的输出只能被匹配到的节点使用，这样整个子图才能被安全地替换。
+    pub fn find<T>(&self, rw: &Rewriter<T>) -> Vec<Match> {
+        let Some(root) = self.nodes.last() else {
+            return Vec::new();
+        };
+
+        let mut used = vec![false; rw.n_node()];
+        let mut ans = Vec::new();
+        for (i, node) in rw.nodes() {
+            if used[i] || node.node.value.name != root.op {
+                continue;
+            }
+            if let Some(m) = self.match_at(rw, i) {
+                if m.nodes.iter().any(|&i| used[i]) {
+                    continue;
+                }
+                for &i in &m.nodes {
+                    used[i] = true
+                }
+                ans.push(m)
+            }
+        }
+        ans
+    }
+
+    /// 尝试以图节点 `root` 为根匹配模式。
+    pub fn match_at<T>(&self, rw: &Rewriter<T>, root: usize) -> Option<Match> {
+        let n = self.nodes.len();
+        let mut map = vec![usize::MAX; n];
+        *map.last_mut()? = root;
+        let map = self.solve(rw, map, vec![false; n])?;
+        // 不同的模式节点必须对应不同的图节点
+        let mut sorted = map.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.len() != n {
+            return None;
+        }
+        // 收集子图输出
+        let outputs = if self.outputs.is_empty() {
+            rw.node(root)?.outputs.clone()
+        } else {
+            self.outputs
+                .iter()
+                .map(|&(p, k)| rw.node(map[p])?.outputs.get(k).copied())
+                .collect::<Option<Vec<_>>>()?
+        };
+        // 子图内部的边不能被外部使用
+        for &i in &map {
+            for e in &rw.node(i)?.outputs {
+                if outputs.contains(e) {
+                    continue;
+                }
+                if rw.is_global_output(*e) || rw.consumers(*e).iter().any(|c| !map.contains(c)) {
+                    return None;
+                }
+            }
+        }
+
+        let inputs = zip(&map, &self.nodes)
+            .flat_map(|(&i, pattern)| {
+                zip(&rw.node(i).unwrap().inputs, &pattern.inputs)
+                    .filter(|(_, input)| matches!(input, PatternInput::Any))
+                    .map(|(&e, _)| e)
+            })
+            .collect();
+        Some(Match {
+            nodes: map.into(),
+            inputs,
+            outputs: outputs.into(),
+        })
+    }
+
+    /// 从已对应的模式节点出发，对应剩下的模式节点。
+    ///
+    /// 已对应的节点通过 [`bind`](Self::bind) 检查并对应其输入的生产者；
+    /// 剩下的节点只能从已对应的输入出发，在使用者中逐个尝试。
+    fn solve<T>(
+        &self,
+        rw: &Rewriter<T>,
+        mut map: Vec<usize>,
+        mut checked: Vec<bool>,
+    ) -> Option<Vec<usize>> {
+        while let Some(p) = (0..map.len()).rfind(|&p| !checked[p] && map[p] != usize::MAX) {
+            if !self.bind(rw, p, map[p], &mut map) {
+                return None;
+            }
+            checked[p] = true
+        }
+        if map.iter().all(|&g| g != usize::MAX) {
+            return Some(map);
+        }
+        // 未对应的节点不被任何已对应的节点使用，找一个输入已对应的节点
+        let p = (0..map.len()).rfind(|&p| {
+            map[p] == usize::MAX
+                && self.nodes[p].inputs.iter().any(
+                    |input| matches!(input, &PatternInput::Output(q, _) if map[q] != usize::MAX),
+                )
+        })?;
+        self.candidates(rw, p, &map).into_iter().find_map(|g| {
+            let mut map = map.clone();
+            map[p] = g;
+            self.solve(rw, map, checked.clone())
+        })
+    }
+
+    /// 将模式节点 `p` 绑定到图节点 `g`，并绑定其输入的生产者。
+    fn bind<T>(&self, rw: &Rewriter<T>, p: usize, g: usize, map: &mut [usize]) -> bool {
+        let pattern = &self.nodes[p];
+        let Some(node) = rw.node(g) else {
+            return false;
+        };
+        if node.node.value.name != pattern.op || node.inputs.len() != pattern.inputs.len() {
+            return false;
+        }
+        map[p] = g;
+        for (&e, input) in zip(&node.inputs, &pattern.inputs) {
+            let &PatternInput::Output(q, k) = input else {
+                continue;
+            };
+            let Some(producer) = rw.producer(e) else {
+                return false;
+            };
+            if rw.node(producer).unwrap().outputs.get(k) != Some(&e) {
+                return false;
+            }
+            match map[q] {
+                usize::MAX => map[q] = producer,
+                bound if bound == producer => {}
+                _ => return false,
+            }
+        }
+        match &pattern.cond {
+            Some(cond) => {
+                let inputs = node
+                    .inputs
+                    .iter()
+                    .map(|&e| &rw.edge(e).meta)
+                    .collect::<Vec<_>>();
+                let outputs = node
+                    .outputs
+                    .iter()
+                    .map(|&e| &rw.edge(e).meta)
+                    .collect::<Vec<_>>();
+                cond(&node.node.value, &inputs, &outputs)
+            }
+            None => true,
+        }
+    }
+
+    /// 模式节点 `p` 可能对应的图节点，即其已绑定输入的未绑定使用者。
+    fn candidates<T>(&self, rw: &Rewriter<T>, p: usize, map: &[usize]) -> Vec<usize> {
+        let mut ans = Vec::new();
+        for (j, input) in self.nodes[p].inputs.iter().enumerate() {
+            let &PatternInput::Output(q, k) = input else {
+                continue;
+            };
+            if map[q] == usize::MAX {
+                continue;
+            }
+            let Some(&e) = rw.node(map[q]).unwrap().outputs.get(k) else {
+                continue;
+            };
+            ans.extend(
+                rw.consumers(e).iter().copied().filter(|&c| {
+                    !map.contains(&c) && rw.node(c).unwrap().inputs.get(j) == Some(&e)
+                }),
+            )
+        }
+        ans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pattern, PatternInput::*, PatternNode};
+    use crate::{Dim, Edge, GraphBuilder, NNGraph, Named, OpInfo, TensorMeta, digit_layout::types};
+    use graph::{TopoBuilder, TopoInput};
+
+    #[test]
+    fn match_sibling() {
+        // n0 = p(x), a = a(n0), b = b(n0), c = c(a)，输出 c 和 b
+        let mut topo = TopoBuilder::new(1);
+        let n0 = topo.push([TopoInput::Edge(0)], 1).unwrap().start;
+        let a = topo.push([TopoInput::Edge(n0)], 1).unwrap().start;
+        let b = topo.push([TopoInput::Edge(n0)], 1).unwrap().start;
+        let c = topo.push([TopoInput::Edge(a)], 1).unwrap().start;
+        let topo = topo.build([c, b]).unwrap();
+
+        let meta = TensorMeta::new(types::F32, [Dim::from(4)]);
+        let edges = (0..topo.n_edge())
+            .map(|_| Edge::<()> {
+                meta: meta.clone(),
+                external: None,
+            })
+            .collect();
+        let nodes = ["p", "a", "b", "c"]
+            .map(|name| Named {
+                name: name.into(),
+                value: OpInfo {
+                    name: name.into(),
+                    arg: None,
+                },
+            })
+            .into();
+        let graph = NNGraph {
+            graph: graph::Graph { topo, nodes, edges },
+            bodies: Box::new([]),
+        };
+        let pm = GraphBuilder::default().pass_manager();
+        let rw = pm.rewriter(graph);
+
+        // b 只经由 n0 连接到根，需要从 n0 在使用者中寻找
+        let mut pattern = Pattern::default();
+        let p0 = pattern.push(PatternNode::new("p", [Any]));
+        let p1 = pattern.push(PatternNode::new("a", [Output(p0, 0)]));
+        let p2 = pattern.push(PatternNode::new("b", [Output(p0, 0)]));
+        let p3 = pattern.push(PatternNode::new("c", [Output(p1, 0)]));
+        pattern.expose(p3, 0).expose(p2, 0);
+
+        let m = pattern.match_at(&rw, 3).unwrap();
+        assert_eq!(&*m.nodes, [0, 1, 2, 3]);
+        assert_eq!(&*m.inputs, [0]);
+        assert_eq!(&*m.outputs, [c, b]);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{Arg, Body, Edge, NNError, NNGraph, OpLib, TensorMeta, op::OpError};
+use graph::{EditError, EditGraph, Named};
+use mem::{Node, Operator};
+use std::{iter::zip, ops::Deref, rc::Rc};
+
+/// 可编辑的计算图。
+///
+/// 在 [`EditGraph`] 的基础上，插入和替换节点时使用注册的算子推导形状。
+/// `repeat` 节点的循环体不参与变换。
+pub struct Rewriter<T> {
+    op_lib: Rc<OpLib>,
+    graph: EditGraph<Node, Edge<T>>,
+    bodies: Box<[Body<T>]>,
+}
+
+/// 可编辑图中的节点。
+pub type EditNode = graph::EditNode<Node>;
+
+impl<T> Deref for Rewriter<T> {
+    type Target = EditGraph<Node, Edge<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.graph
+    }
+}
+
+impl<T> Rewriter<T> {
+    pub(crate) fn new(graph: NNGraph<T>, op_lib: Rc<OpLib>) -> Self {
+        let NNGraph { graph, bodies } = graph;
+        Self {
+            op_lib,
+            graph: graph.into(),
+            bodies,
+        }
+    }
+
+    /// 插入新节点，推导形状并为其创建新的输出边，返回节点序号。
+    pub fn insert(
+        &mut self,
+        name: impl ToString,
+        op: impl ToString,
+        arg: Option<Arg>,
+        inputs: impl IntoIterator<Item = usize>,
+    ) -> Result<usize, NNError> {
+        let name = name.to_string();
+        let op = op.to_string();
+        let inputs = inputs.into_iter().collect::<Vec<_>>();
+
+        let outputs = self
+            .infer(&name, &op, arg.as_ref(), &inputs)?
+            .into_iter()
+            .map(|meta| {
+                self.graph.add_edge(Edge {
+                    meta,
+                    external: None,
+                })
+            })
+            .collect();
+
+        Ok(self.push(name, Operator { name: op, arg }, inputs, outputs))
+    }
+
+    /// 用一个新节点替换匹配到的子图，新节点接管原子图根节点的输出边，
+    /// 因此这些边上的外部绑定和下游连接都保持不变。
+    ///
+    /// `name` 为空时沿用子图根节点的名字。
+    pub fn replace(
+        &mut self,
+        m: &super::Match,
+        name: impl ToString,
+        op: impl ToString,
+        arg: Option<Arg>,
+        inputs: impl IntoIterator<Item = usize>,
+    ) -> Result<usize, NNError> {
+        let &root = m.nodes.last().unwrap();
+        let mut name = name.to_string();
+        if name.is_empty() {
+            name = self.node(root).unwrap().node.name.clone()
+        }
+        let op = op.to_string();
+        let inputs = inputs.into_iter().collect::<Vec<_>>();
+
+        let meta = self.infer(&name, &op, arg.as_ref(), &inputs)?;
+        if meta.len() != m.outputs.len()
+            || zip(&meta, &*m.outputs).any(|(meta, &e)| *meta != self.edge(e).meta)
+        {
+            return Err(NNError {
+                name,
+                err: OpError::ShapeMismatch,
+            });
+        }
+
+        for &i in &m.nodes {
+            self.remove(i);
+        }
+        Ok(self.push(name, Operator { name: op, arg }, inputs, m.outputs.to_vec()))
+    }
+
+    /// 删除节点，其输出边失去生产者。
+    pub fn remove(&mut self, i: usize) -> Option<EditNode> {
+        self.graph.remove(i)
+    }
+
+    /// 将所有对边 `old` 的使用（包括全图输出）重定向到边 `new`，`new` 依赖 `old` 时报错。
+    pub fn rewire(&mut self, old: usize, new: usize) -> Result<(), NNError> {
+        if old != new && self.graph.depends_on(new, old) {
+            let node = self.producer(new).unwrap();
+            return Err(self.topo_error(EditError::Cycle { node }));
+        }
+        self.graph.rewire(old, new);
+        Ok(())
+    }
+
+    /// 拓扑排序，尽量保持节点的原有顺序。
+    pub fn topo_order(&self) -> Result<Vec<usize>, NNError> {
+        self.graph.topo_order().map_err(|err| self.topo_error(err))
+    }
+
+    /// 转换回逻辑连接图，节点按依赖关系重新拓扑排序。
+    ///
+    /// 删除节点后仍被使用的边没有生产者，此时报错。
+    pub fn into_graph(self) -> Result<NNGraph<T>, NNError> {
+        let order = self.topo_order()?;
+        self.graph
+            .check(&order)
+            .map_err(|err| self.topo_error(err))?;
+        Ok(NNGraph {
+            graph: self.graph.into_graph_in(order).unwrap(),
+            bodies: self.bodies,
+        })
+    }
+
+    /// 以出错的节点命名图结构错误。
+    fn topo_error(&self, err: EditError) -> NNError {
+        let name = match err {
+            EditError::Cycle { node }
+            | EditError::Dangling { node, .. }
+            | EditError::UseBeforeDefine { node, .. }
+            | EditError::NodeOrder { node } => self
+                .node(node)
+                .map_or_else(|| format!("node{node}"), |node| node.node.name.clone()),
+            EditError::OutputNotProduced { index, .. } => format!("output{index}"),
+        };
+        NNError {
+            name,
+            err: OpError::TopoError,
+        }
+    }
+
+    fn infer(
+        &self,
+        name: &str,
+        op: &str,
+        arg: Option<&Arg>,
+        inputs: &[usize],
+    ) -> Result<Vec<TensorMeta>, NNError> {
+        let Some(infer) = self.op_lib.get(op) else {
+            return Err(NNError {
+                name: name.into(),
+                err: OpError::NotExist,
+            });
+        };
+        let meta = inputs
+            .iter()
+            .map(|&e| self.edge(e).meta.clone())
+            .collect::<Vec<_>>();
+        infer.infer(&meta, arg).map_err(|err| NNError {
+            name: name.into(),
+            err,
+        })
+    }
+
+    fn push(
+        &mut self,
+        name: String,
+        operator: Operator,
+        inputs: Vec<usize>,
+        outputs: Vec<usize>,
+    ) -> usize {
+        self.graph.insert(
+            Named {
+                name,
+                value: operator,
+            },
+            inputs,
+            outputs,
+        )
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{Arg, Edge, External, NNGraph, Node, Tensor};
+use graph::{EditGraph, NodeRef};
+use std::{
+    collections::HashMap,
+    iter::{once, zip},
+};
+
+/// `repeat` 节点引用的循环体。
+///
+/// 循环体是一个没有嵌套循环的计算图，输入为 `[循环变量.., 共享输入..]`，输出为更新后的循环变量。
+/// 循环体中的外部张量绑定到第 0 次迭代的外部张量，其他迭代的外部张量保存在 `bindings` 中。
+#[derive(Clone)]
+pub struct Body<T> {
+    /// 每次迭代的命名空间前缀，第 `i` 次迭代的命名空间为 `{namespace}{i}`。
+    pub namespace: String,
+    pub graph: NNGraph<T>,
+    /// 第 1 次及之后每次迭代绑定的外部张量。
+    pub bindings: Box<[Bindings<External<T>>]>,
+}
+
+/// 一次迭代绑定的外部张量。
+#[derive(Clone)]
+pub struct Bindings<E> {
+    /// 作为输入加载的外部张量，例如权重，按循环体中这些边的序号排列。
+    pub inputs: Box<[E]>,
+    /// 绑定到节点输出的外部张量，例如循环状态的更新，按循环体中这些边的序号排列。
+    pub outputs: Box<[E]>,
+}
+
+/// 下降到存储管理图的循环体。
+pub struct LoweredBody<U> {
+    pub graph: mem::Graph<U>,
+    /// 第 1 次及之后每次迭代绑定的外部张量。
+    pub bindings: Box<[Bindings<Tensor<U, 2>>]>,
+}
+
+impl<E> Bindings<E> {
+    pub fn map<U>(self, mut f: impl FnMut(E) -> U) -> Bindings<U> {
+        let Self { inputs, outputs } = self;
+        Bindings {
+            inputs: inputs.into_iter().map(&mut f).collect(),
+            outputs: outputs.into_iter().map(&mut f).collect(),
+        }
+    }
+}
+
+impl<T> Bindings<External<T>> {
+    /// 取出图中绑定的所有外部张量。
+    pub(crate) fn take(graph: &mut graph::Graph<Node, Edge<T>>) -> Self {
+        let Bindings { inputs, outputs } = bound_edges(graph);
+        let mut take = |edges: Box<[usize]>| {
+            edges
+                .into_iter()
+                .map(|e| graph.edges[e].external.take().unwrap())
+                .collect()
+        };
+        Self {
+            inputs: take(inputs),
+            outputs: take(outputs),
+        }
+    }
+}
+
+/// 图中绑定了外部张量的边，分为没有生产者的输入和节点的输出。
+fn bound_edges<T>(graph: &graph::Graph<Node, Edge<T>>) -> Bindings<usize> {
+    let mut produced = vec![false; graph.edges.len()];
+    for NodeRef { outputs, .. } in graph.topo.iter() {
+        for e in outputs {
+            produced[e] = true
+        }
+    }
+    let (outputs, inputs) = (0..graph.edges.len())
+        .filter(|&e| graph.edges[e].external.is_some())
+        .partition::<Vec<_>, _>(|&e| produced[e]);
+    Bindings {
+        inputs: inputs.into(),
+        outputs: outputs.into(),
+    }
+}
+
+impl<T> Body<T> {
+    /// 迭代次数。
+    pub fn times(&self) -> usize {
+        self.bindings.len() + 1
+    }
+
+    /// 循环变量的数量。
+    pub fn n_carried(&self) -> usize {
+        self.graph.graph.topo.n_outputs()
+    }
+
+    /// 保持循环，单独下降循环体。
+    pub fn lower<U>(
+        self,
+        value: &HashMap<&str, usize>,
+        mut map: impl FnMut(T) -> Tensor<U, 2>,
+    ) -> LoweredBody<U> {
+        let Self {
+            graph, bindings, ..
+        } = self;
+        let bindings = bindings
+            .into_iter()
+            .map(|bindings| bindings.map(|e| map(e.item)))
+            .collect();
+        LoweredBody {
+            graph: graph.lower(value, map),
+            bindings,
+        }
+    }
+}
+
+impl<T> NNGraph<T> {
+    /// 展开所有 `repeat` 节点，得到与逐层构造相同的平坦计算图。
+    pub fn unroll(self) -> Self {
+        let Self { graph, bodies } = self;
+        if bodies.is_empty() {
+            return Self { graph, bodies };
+        }
+
+        let mut bodies = bodies.into_iter().map(Some).collect::<Vec<_>>();
+        let mut graph = EditGraph::from(graph);
+        let repeats = graph
+            .nodes()
+            .filter(|(_, node)| node.node.value.name == "repeat")
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        for i in repeats {
+            let node = graph.remove(i).unwrap();
+            let Some(Arg::Dict(arg)) = &node.node.value.arg else {
+                unreachable!()
+            };
+            let body = bodies[arg["body"].to_usize()].take().unwrap();
+            let outputs = instantiate(&mut graph, body, node.inputs);
+            for (old, new) in zip(node.outputs, outputs) {
+                graph.rewire(old, new)
+            }
+        }
+
+        Self {
+            graph: graph.into_graph().unwrap(),
+            bodies: Box::new([]),
+        }
+    }
+}
+
+/// 将循环体的每次迭代依次插入到图中，返回最后一次迭代的输出。
+fn instantiate<T>(
+    graph: &mut EditGraph<Node, Edge<T>>,
+    body: Body<T>,
+    inputs: Vec<usize>,
+) -> Vec<usize> {
+    let Body {
+        namespace,
+        graph: body,
+        bindings,
+    } = body;
+    let mut body = body.graph;
+    // 第 0 次迭代的外部张量从循环体中取出
+    let positions = bound_edges(&body);
+    let first = Bindings::take(&mut body);
+    let graph::Graph { topo, nodes, edges } = body;
+    let n_carried = topo.n_outputs();
+    let prefix = format!("{namespace}0");
+
+    let mut carried = inputs;
+    for (i, bindings) in once(first).chain(bindings).enumerate() {
+        // 按边序号放回此次迭代的外部张量
+        let mut externals = edges.iter().map(|_| None).collect::<Vec<_>>();
+        for (&e, external) in
+            zip(&positions.inputs, bindings.inputs).chain(zip(&positions.outputs, bindings.outputs))
+        {
+            externals[e] = Some(external)
+        }
+        let mut edge_map = vec![usize::MAX; edges.len()];
+        edge_map[..carried.len()].copy_from_slice(&carried);
+        for (topo, node) in zip(topo.iter(), &nodes) {
+            let NodeRef { inputs, outputs } = topo;
+            let inputs = inputs
+                .iter()
+                .map(|&e| match edge_map[e] {
+                    usize::MAX => {
+                        // 未映射，是此次迭代的权重
+                        let j = graph.add_local(Edge {
+                            meta: edges[e].meta.clone(),
+                            external: externals[e].take(),
+                        });
+                        edge_map[e] = j;
+                        j
+                    }
+                    j => j,
+                })
+                .collect();
+            let outputs = outputs
+                .map(|e| {
+                    let j = graph.add_edge(Edge {
+                        meta: edges[e].meta.clone(),
+                        external: externals[e].take(),
+                    });
+                    edge_map[e] = j;
+                    j
+                })
+                .collect();
+            let mut node = node.clone();
+            if let Some(rest) = node.name.strip_prefix(&prefix) {
+                node.name = format!("{namespace}{i}{rest}")
+            }
+            graph.insert(node, inputs, outputs);
+        }
+        for (k, &e) in topo.global_outputs().iter().enumerate() {
+            carried[k] = edge_map[e]
+        }
+    }
+    carried.truncate(n_carried);
+    carried
+}
@@ -0,0 +1,220 @@
+use crate::{Arg, GraphBuilder, NNError, NNGraph, TensorMeta, op::OpError};
+use graph::TopoError;
+use std::iter::zip;
+
+/// 计算图检查发现的错误。
+#[derive(Debug)]
+pub enum GraphError {
+    /// 拓扑结构错误。
+    Topo(TopoError),
+    /// 节点数或边数与拓扑结构不符，此时不再检查节点。
+    Size {
+        n_node: usize,
+        n_edge: usize,
+        expected_n_node: usize,
+        expected_n_edge: usize,
+    },
+    /// 节点的算子未注册，或形状推导失败。
+    Infer { node: usize, err: NNError },
+    /// 推导出的输出数量与节点的输出数量不符。
+    OutputCount {
+        node: usize,
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// 推导出的第 `slot` 个输出与图中记录的数据类型或形状不符。
+    OutputMeta {
+        node: usize,
+        name: String,
+        slot: usize,
+    },
+    /// 第 `body` 个循环体中的错误。
+    Body {
+        body: usize,
+        errors: Vec<GraphError>,
+    },
+}
+
+impl GraphBuilder {
+    /// 检查计算图的拓扑结构，并用此构造器中注册的算子重新推导每个节点的输出，
+    /// `repeat` 节点的输出由循环体决定，循环体也会被检查。
+    /// 返回发现的所有错误。
+    pub fn validate<T>(&self, graph: &NNGraph<T>) -> Result<(), Vec<GraphError>> {
+        let graph::Graph { topo, nodes, edges } = &graph.graph;
+
+        topo.validate()
+            .map_err(|errors| errors.into_iter().map(GraphError::Topo).collect::<Vec<_>>())?;
+        if nodes.len() != topo.n_node() || edges.len() != topo.n_edge() {
+            return Err(vec![GraphError::Size {
+                n_node: nodes.len(),
+                n_edge: edges.len(),
+                expected_n_node: topo.n_node(),
+                expected_n_edge: topo.n_edge(),
+            }]);
+        }
+
+        let op_lib = self.op_lib();
+        let mut errors = Vec::new();
+        for (i, (topo, node)) in zip(topo.iter(), nodes).enumerate() {
+            let name = &node.name;
+            let inputs = topo
+                .inputs
+                .iter()
+                .map(|&e| edges[e].meta.clone())
+                .collect::<Vec<_>>();
+            let arg = node.value.arg.as_ref();
+            let outputs = match &*node.value.name {
+                "repeat" => infer_repeat(graph, &inputs, arg),
+                op => match op_lib.get(op) {
+                    Some(op) => op.infer(&inputs, arg),
+                    None => Err(OpError::NotExist),
+                },
+            };
+            let outputs = match outputs {
+                Ok(outputs) => outputs,
+                Err(err) => {
+                    errors.push(GraphError::Infer {
+                        node: i,
+                        err: NNError {
+                            name: name.clone(),
+                            err,
+                        },
+                    });
+                    continue;
+                }
+            };
+
+            if outputs.len() != topo.outputs.len() {
+                errors.push(GraphError::OutputCount {
+                    node: i,
+                    name: name.clone(),
+                    expected: topo.outputs.len(),
+                    actual: outputs.len(),
+                });
+                continue;
+            }
+            for (slot, (meta, e)) in zip(outputs, topo.outputs).enumerate() {
+                if meta != edges[e].meta {
+                    errors.push(GraphError::OutputMeta {
+                        node: i,
+                        name: name.clone(),
+                        slot,
+                    })
+                }
+            }
+        }
+
+        for (i, body) in graph.bodies.iter().enumerate() {
+            if let Err(body_errors) = self.validate(&body.graph) {
+                errors.push(GraphError::Body {
+                    body: i,
+                    errors: body_errors,
+                })
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// `repeat` 节点的输入必须与循环体的输入相同，输出为循环体的输出。
+fn infer_repeat<T>(
+    graph: &NNGraph<T>,
+    inputs: &[TensorMeta],
+    arg: Option<&Arg>,
+) -> Result<Vec<TensorMeta>, OpError> {
+    let Some(Arg::Dict(arg)) = arg else {
+        return Err(OpError::ArgError);
+    };
+    let Some(&Arg::Int(body)) = arg.get("body") else {
+        return Err(OpError::ArgError);
+    };
+    let Some(body) = graph.bodies.get(body as usize) else {
+        return Err(OpError::ArgError);
+    };
+    let graph::Graph { topo, edges, .. } = &body.graph.graph;
+    if topo.n_inputs() != inputs.len()
+        || zip(topo.global_inputs(), inputs).any(|(e, meta)| edges[e].meta != *meta)
+    {
+        return Err(OpError::ShapeMismatch);
+    }
+    Ok(topo
+        .global_outputs()
+        .iter()
+        .map(|&e| edges[e].meta.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GraphError;
+    use crate::{
+        Arg, Dim, Edge, GraphBuilder, NNGraph, Named, OpInfo, TensorMeta,
+        digit_layout::types,
+        op::{self, OpError},
+    };
+    use graph::{TopoBuilder, TopoInput::*};
+
+    #[test]
+    fn malformed_nodes() {
+        // transpose 的 perm 越界，concat 的输入维数不同
+        let mut topo = TopoBuilder::new(2);
+        let t = topo.push([Edge(0)], 1).unwrap().start;
+        let c = topo.push([Edge(0), Edge(1)], 1).unwrap().start;
+        let topo = topo.build([t, c]).unwrap();
+
+        let n = Dim::from("n");
+        let x = TensorMeta::new(types::F32, [n.clone(), Dim::from(4)]);
+        let y = TensorMeta::new(types::F32, [n.clone()]);
+        let edges = [x.clone(), y, x.clone(), x]
+            .map(|meta| Edge::<()> {
+                meta,
+                external: None,
+            })
+            .into();
+        let nodes = [
+            (
+                "transpose",
+                Some(Arg::dict([(
+                    "perm".into(),
+                    Arg::arr([Arg::int(0), Arg::int(2)]),
+                )])),
+            ),
+            ("concat", Some(Arg::int(0))),
+        ]
+        .map(|(name, arg)| Named {
+            name: name.into(),
+            value: OpInfo {
+                name: name.into(),
+                arg,
+            },
+        })
+        .into();
+        let graph = NNGraph {
+            graph: graph::Graph { topo, nodes, edges },
+            bodies: Box::new([]),
+        };
+
+        let mut builder = GraphBuilder::default();
+        builder
+            .register_op("transpose", op::transpose::Transpose)
+            .register_op("concat", op::concat::Concat);
+        let errors = builder.validate(&graph).unwrap_err();
+        let errors = errors
+            .iter()
+            .map(|e| match e {
+                GraphError::Infer { node, err } => (*node, err.err),
+                _ => panic!("unexpected error: {e:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            &*errors,
+            [(0, OpError::ArgError), (1, OpError::ShapeError)]
+        ))
+    }
+}
@@ -87,7 +87,7 @@ impl<T> BlobAnalyzer<T> {
 
     /// 标记 `blob` 在第 `i_node` 号节点处仍存在。
     fn record(&mut self, blob: &Rc<Info<T>>, i_node: usize) {
-        if let Info::Internal(_) = **blob {
+        if let Info::Internal(..) = **blob {
             use std::collections::hash_map::Entry::{Occupied, Vacant};
             match self.0.entry(KeyWeak::from(blob)) {
                 Occupied(mut entry) => {
@@ -1,8 +1,13 @@
-﻿use super::{action::Operation, key_weak::KeyWeak};
-use crate::{Graph, Info, analyze::action::Action};
+﻿use super::{
+    action::Operation,
+    key_weak::KeyWeak,
+    strategy::{self, Item, Strategy},
+};
+use crate::{Graph, Info, Pool, analyze::action::Action};
 use std::{
     cmp::Ordering,
     collections::{BTreeSet, HashMap},
+    fmt,
     ops::Range,
 };
 
@@ -11,28 +16,243 @@ pub struct MemRangeMap<T> {
     pub map: HashMap<KeyWeak<Info<T>>, Range<usize>>,
 }
 
+/// 存储空间不足，无法分配块。
+pub struct AllocError<T> {
+    /// 分配失败的块。
+    pub blob: KeyWeak<Info<T>>,
+    pub size: usize,
+    /// 需要分配此块的节点，离线策略中为此块存在期间占用最多的节点。
+    pub i_node: usize,
+    /// 失败时已占用的空间。
+    pub peak: usize,
+}
+
+impl<T> fmt::Debug for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AllocError")
+            .field("blob", &self.blob.as_ptr())
+            .field("size", &self.size)
+            .field("i_node", &self.i_node)
+            .field("peak", &self.peak)
+            .finish()
+    }
+}
+
+/// 按存储池分别计算地址区间时的错误。
+pub enum PoolError<T> {
+    /// 存在块的存储池没有参数。
+    NoConfig(Pool),
+    /// 存储池中的空间不足。
+    Alloc(Pool, AllocError<T>),
+}
+
+impl<T> fmt::Debug for PoolError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoConfig(pool) => f.debug_tuple("NoConfig").field(pool).finish(),
+            Self::Alloc(pool, err) => f.debug_tuple("Alloc").field(pool).field(err).finish(),
+        }
+    }
+}
+
 impl<T> Graph<T> {
-    pub fn mem_range_map(&self, max_size: usize, alignment: usize) -> MemRangeMap<T> {
+    /// 使用 [`Strategy::BestFit`] 计算每个块的地址区间，空间不足时返回第一个无法分配的块。
+    pub fn mem_range_map(
+        &self,
+        max_size: usize,
+        alignment: usize,
+    ) -> Result<MemRangeMap<T>, AllocError<T>> {
+        self.mem_range_map_with(Strategy::BestFit, max_size, alignment)
+    }
+
+    /// 使用指定的策略计算每个块的地址区间，所有存储池共用一个地址空间。
+    pub fn mem_range_map_with(
+        &self,
+        strategy: Strategy,
+        max_size: usize,
+        alignment: usize,
+    ) -> Result<MemRangeMap<T>, AllocError<T>> {
+        self.plan(
+            None,
+            PoolConfig {
+                strategy,
+                max_size,
+                alignment,
+            },
+        )
+    }
+
+    /// 分别计算每个存储池中的块的地址区间，第 `i` 个存储池使用 `configs[i]`。
+    pub fn mem_range_maps(
+        &self,
+        configs: &[PoolConfig],
+    ) -> Result<Box<[MemRangeMap<T>]>, PoolError<T>> {
+        if let Some(pool) = self.pools().into_iter().find(|p| p.0 >= configs.len()) {
+            return Err(PoolError::NoConfig(pool));
+        }
+        configs
+            .iter()
+            .enumerate()
+            .map(|(i, &config)| {
+                let pool = Pool(i);
+                self.plan(Some(pool), config)
+                    .map_err(|err| PoolError::Alloc(pool, err))
+            })
+            .collect()
+    }
+
+    /// 计算 `pool` 中的块的地址区间，`pool` 为 [`None`] 时计算所有块。
+    pub(crate) fn plan(
+        &self,
+        pool: Option<Pool>,
+        config: PoolConfig,
+    ) -> Result<MemRangeMap<T>, AllocError<T>> {
+        let PoolConfig {
+            strategy,
+            max_size,
+            alignment,
+        } = config;
+        let offline = match strategy {
+            Strategy::BestFit => return self.best_fit(pool, max_size, alignment),
+            Strategy::GreedyBySize => strategy::greedy_by_size,
+            Strategy::IntervalColoring => strategy::interval_coloring,
+            Strategy::GreedyWithSearch => strategy::greedy_with_search,
+        };
+        let items = self.items(pool, alignment);
+        let offsets = offline(&items);
+        match strategy::overflow(&items, &offsets, max_size) {
+            Some(err) => Err(err),
+            None => Ok(strategy::collect(items, offsets)),
+        }
+    }
+
+    /// 用所有策略计算一次，比较占用的总空间。
+    pub fn compare_strategies(&self, alignment: usize) -> StrategyReport {
+        let items = self.items(None, alignment);
+        StrategyReport {
+            lower_bound: strategy::lower_bound(&items),
+            n_blobs: items.len(),
+            searched: items.len() <= Strategy::SEARCH_LIMIT,
+            peaks: Strategy::ALL
+                .into_iter()
+                .map(|s| {
+                    (
+                        s,
+                        self.mem_range_map_with(s, usize::MAX, alignment)
+                            .unwrap()
+                            .range
+                            .len(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn items(&self, pool: Option<Pool>, alignment: usize) -> Vec<Item<T>> {
+        // 排序使结果不受哈希表遍历顺序的影响
+        let mut lt = self.blob_lifetime();
+        lt.sort_unstable();
+        lt.into_iter()
+            .filter(|blt| in_pool(&blt.blob, pool))
+            .map(|blt| Item::new(blt, alignment))
+            .collect()
+    }
+
+    fn best_fit(
+        &self,
+        pool: Option<Pool>,
+        max_size: usize,
+        alignment: usize,
+    ) -> Result<MemRangeMap<T>, AllocError<T>> {
         let mut calculator = OffsetCalculator::new(alignment);
         calculator.put(0..max_size / alignment * alignment);
 
         let actions = self.to_actions();
         let mut map = HashMap::with_capacity(actions.len() / 2);
-        for Action { op, blob, .. } in actions {
+        for Action { i_node, op, blob } in actions {
+            if !in_pool(&blob, pool) {
+                continue;
+            }
             match op {
                 Operation::Alloc => {
-                    let Some(&Info::Internal(size)) = blob.upgrade().as_deref() else {
+                    let Some(&Info::Internal(size, _)) = blob.upgrade().as_deref() else {
                         panic!()
                     };
-                    assert!(map.insert(blob, calculator.take(size).unwrap()).is_none())
+                    let Some(range) = calculator.take(size) else {
+                        return Err(AllocError {
+                            blob,
+                            size,
+                            i_node,
+                            peak: calculator.taken_range.len(),
+                        });
+                    };
+                    assert!(map.insert(blob, range).is_none())
                 }
                 Operation::Free => calculator.put(map[&blob].clone()),
             }
         }
-        MemRangeMap {
+        Ok(MemRangeMap {
             range: calculator.taken_range,
             map,
+        })
+    }
+}
+
+/// 块是否在存储池 `pool` 中，`pool` 为 [`None`] 时总是成立。
+pub(super) fn in_pool<T>(blob: &KeyWeak<Info<T>>, pool: Option<Pool>) -> bool {
+    match (pool, blob.upgrade().as_deref()) {
+        (None, _) => true,
+        (Some(pool), Some(&Info::Internal(_, p))) => p == pool,
+        (Some(_), _) => false,
+    }
+}
+
+/// 一个存储池的分配参数。
+#[derive(Clone, Copy, Debug)]
+pub struct PoolConfig {
+    pub strategy: Strategy,
+    pub max_size: usize,
+    pub alignment: usize,
+}
+
+/// 各策略占用的总空间。
+pub struct StrategyReport {
+    /// 任意时刻同时存在的块的总大小的最大值，任何策略都不能低于此值。
+    pub lower_bound: usize,
+    /// 参与放置的块数。
+    pub n_blobs: usize,
+    /// [`Strategy::GreedyWithSearch`] 是否进行了穷举，块数超过 [`Strategy::SEARCH_LIMIT`] 时其结果与
+    /// [`Strategy::GreedyBySize`] 相同。
+    pub searched: bool,
+    pub peaks: Vec<(Strategy, usize)>,
+}
+
+impl StrategyReport {
+    /// 占用空间最小的策略。
+    pub fn best(&self) -> Strategy {
+        self.peaks
+            .iter()
+            .min_by_key(|(_, peak)| *peak)
+            .map_or_else(Strategy::default, |&(s, _)| s)
+    }
+}
+
+impl fmt::Display for StrategyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<18}{}", "lower bound", self.lower_bound)?;
+        for (strategy, peak) in &self.peaks {
+            write!(f, "{:<18}{peak}", format!("{strategy:?}"))?;
+            if *strategy == Strategy::GreedyWithSearch && !self.searched {
+                write!(
+                    f,
+                    " (not searched: {} blobs > {})",
+                    self.n_blobs,
+                    Strategy::SEARCH_LIMIT
+                )?
+            }
+            writeln!(f)?
         }
+        Ok(())
     }
 }
 
@@ -2,18 +2,23 @@
 mod key_weak;
 mod life_time;
 mod mem_range;
+mod report;
+mod strategy;
 
 pub use action::Action;
 pub use key_weak::KeyWeak;
 pub use life_time::BlobLifeTime;
-pub use mem_range::MemRangeMap;
+pub use mem_range::{AllocError, MemRangeMap, PoolConfig, PoolError, StrategyReport};
+pub use report::{BlobReport, MemReport};
+pub use strategy::Strategy;
 
 pub fn print_lifetime<T>(lt: &[BlobLifeTime<T>]) {
     for (i, BlobLifeTime { blob, life_time }) in lt.iter().enumerate() {
-        let Some(&crate::Info::Internal(size)) = blob.upgrade().as_deref() else {
-            panic!()
-        };
-        print!("{i:>3} {size:6} ");
+        match blob.upgrade().as_deref() {
+            Some(&crate::Info::Internal(size, _)) => print!("{i:>3} {size:6} "),
+            Some(crate::Info::External(_)) => print!("{i:>3} {:>6} ", "ext"),
+            None => print!("{i:>3} {:>6} ", "-"),
+        }
         for _ in 0..life_time.start {
             print!(" ")
         }
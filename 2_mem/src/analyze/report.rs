@@ -0,0 +1,274 @@
+use super::{MemRangeMap, key_weak::KeyWeak, mem_range::in_pool};
+use crate::{BlobLifeTime, Graph, Info, Pool};
+use graph::NodeRef;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+    iter::zip,
+    ops::Range,
+};
+
+/// 存储规划报告。
+pub struct MemReport {
+    /// 分配的总空间。
+    pub total: usize,
+    /// 同时存在的块的总大小的最大值。
+    pub peak: usize,
+    /// 达到最大值的节点。
+    pub peak_node: usize,
+    /// 按大小降序排列的块。
+    pub blobs: Vec<BlobReport>,
+}
+
+/// 单个块的信息。
+pub struct BlobReport {
+    pub size: usize,
+    /// 分配到的地址区间，大小为 0 的块没有地址。
+    pub range: Option<Range<usize>>,
+    pub life_time: Range<usize>,
+    /// 产生此块的节点名字，全图输入没有生产者。
+    pub producer: Option<String>,
+    /// 产生此块的算子。
+    pub operator: Option<String>,
+}
+
+impl<T> Graph<T> {
+    /// 根据存储池 `pool` 的地址分配结果生成报告，`pool` 为 [`None`] 时 `map` 是所有块共同分配的结果。
+    pub fn mem_report(&self, pool: Option<Pool>, map: &MemRangeMap<T>) -> MemReport {
+        let Self(graph::Graph { topo, nodes, edges }) = self;
+
+        let mut producers = HashMap::new();
+        for (topo, node) in zip(topo.iter(), nodes) {
+            if node.value.name == "empty" {
+                continue;
+            }
+            let NodeRef { outputs, .. } = topo;
+            for e in outputs {
+                producers
+                    .entry(KeyWeak::from(edges[e].get()))
+                    .or_insert(node);
+            }
+        }
+
+        let lt = self
+            .blob_lifetime()
+            .into_iter()
+            .filter(|blt| in_pool(&blt.blob, pool))
+            .collect::<Vec<_>>();
+        let (peak, peak_node) = live_peak(&lt);
+        let mut blobs = lt
+            .into_iter()
+            .map(|BlobLifeTime { blob, life_time }| {
+                let Some(&Info::Internal(size, _)) = blob.upgrade().as_deref() else {
+                    unreachable!()
+                };
+                let node = producers.get(&blob);
+                BlobReport {
+                    size,
+                    range: map.map.get(&blob).filter(|r| !r.is_empty()).cloned(),
+                    life_time,
+                    producer: node.map(|n| n.name.clone()),
+                    operator: node.map(|n| n.value.name.clone()),
+                }
+            })
+            .collect::<Vec<_>>();
+        blobs.sort_by(|a, b| {
+            b.size
+                .cmp(&a.size)
+                .then(a.life_time.start.cmp(&b.life_time.start))
+        });
+
+        MemReport {
+            total: map.range.len(),
+            peak,
+            peak_node,
+            blobs,
+        }
+    }
+}
+
+impl MemReport {
+    /// 碎片率，即分配的总空间中不可能同时被使用的比例。
+    pub fn fragmentation(&self) -> f64 {
+        if self.total == 0 {
+            0.
+        } else {
+            1. - self.peak as f64 / self.total as f64
+        }
+    }
+
+    /// 最大的 `n` 个块。
+    pub fn top(&self, n: usize) -> &[BlobReport] {
+        &self.blobs[..n.min(self.blobs.len())]
+    }
+
+    /// 按生产者的命名空间统计块的总大小，只保留命名空间的前 `depth` 级，按大小降序排列。
+    pub fn by_namespace(&self, depth: usize) -> Vec<(String, usize)> {
+        self.group_by(|blob| {
+            let name = blob.producer.as_deref()?;
+            let path = name.split_once(':').map_or(name, |(path, _)| path);
+            Some(path.split('.').take(depth).collect::<Vec<_>>().join("."))
+        })
+    }
+
+    /// 按生产者的算子统计块的总大小，按大小降序排列。
+    pub fn by_operator(&self) -> Vec<(String, usize)> {
+        self.group_by(|blob| blob.operator.clone())
+    }
+
+    fn group_by(&self, mut key: impl FnMut(&BlobReport) -> Option<String>) -> Vec<(String, usize)> {
+        let mut map = BTreeMap::<String, usize>::new();
+        for blob in &self.blobs {
+            let key = key(blob).unwrap_or_else(|| "<input>".into());
+            *map.entry(key).or_default() += blob.size
+        }
+        let mut ans = map.into_iter().collect::<Vec<_>>();
+        ans.sort_by(|(_, a), (_, b)| b.cmp(a));
+        ans
+    }
+
+    /// 导出为 JSON。
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        write!(
+            json,
+            r#"{{"total":{},"peak":{},"peak_node":{},"fragmentation":{},"blobs":["#,
+            self.total,
+            self.peak,
+            self.peak_node,
+            self.fragmentation(),
+        )
+        .unwrap();
+        for (i, blob) in self.blobs.iter().enumerate() {
+            if i > 0 {
+                json.push(',')
+            }
+            write!(
+                json,
+                r#"{{"size":{},"offset":{},"life_time":[{},{}],"producer":{},"operator":{}}}"#,
+                blob.size,
+                blob.range
+                    .as_ref()
+                    .map_or("null".into(), |r| r.start.to_string()),
+                blob.life_time.start,
+                blob.life_time.end,
+                json_str(blob.producer.as_deref()),
+                json_str(blob.operator.as_deref()),
+            )
+            .unwrap()
+        }
+        json.push_str("]}");
+        json
+    }
+
+    /// 导出为 SVG，横轴为节点序号，纵轴为地址。
+    pub fn to_svg(&self) -> String {
+        const NODE_WIDTH: usize = 8;
+        const HEIGHT: usize = 600;
+
+        let n_node = self
+            .blobs
+            .iter()
+            .map(|b| b.life_time.end + 1)
+            .max()
+            .unwrap_or(0);
+        let base = self
+            .blobs
+            .iter()
+            .filter_map(|b| b.range.as_ref().map(|r| r.start))
+            .min()
+            .unwrap_or(0);
+        let scale = HEIGHT as f64 / self.total.max(1) as f64;
+        let width = n_node * NODE_WIDTH;
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{HEIGHT}">"#
+        )
+        .unwrap();
+        for (i, blob) in self.blobs.iter().enumerate() {
+            let Some(range) = &blob.range else { continue };
+            let x = blob.life_time.start * NODE_WIDTH;
+            let w = blob.life_time.len() * NODE_WIDTH + NODE_WIDTH;
+            let y = (range.start - base) as f64 * scale;
+            let h = range.len() as f64 * scale;
+            let hue = i * 47 % 360;
+            writeln!(
+                svg,
+                r#"<rect x="{x}" y="{y:.2}" width="{w}" height="{h:.2}" fill="hsl({hue},60%,60%)" stroke="black" stroke-width="0.5"><title>{} {} [{}, {}]</title></rect>"#,
+                xml_escape(blob.producer.as_deref().unwrap_or("<input>")),
+                blob.size,
+                blob.life_time.start,
+                blob.life_time.end,
+            )
+            .unwrap()
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl fmt::Display for MemReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total         {}", self.total)?;
+        writeln!(f, "peak          {} at node {}", self.peak, self.peak_node)?;
+        writeln!(f, "fragmentation {:.2}%", self.fragmentation() * 100.)?;
+        for blob in self.top(10) {
+            writeln!(
+                f,
+                "{:>12} {:>4}..={:<4} {}",
+                blob.size,
+                blob.life_time.start,
+                blob.life_time.end,
+                blob.producer.as_deref().unwrap_or("<input>"),
+            )?
+        }
+        Ok(())
+    }
+}
+
+/// 同时存在的块的总大小的最大值，以及达到最大值的节点。
+fn live_peak<T>(lt: &[BlobLifeTime<T>]) -> (usize, usize) {
+    let mut delta = BTreeMap::<usize, isize>::new();
+    for BlobLifeTime { blob, life_time } in lt {
+        let Some(&Info::Internal(size, _)) = blob.upgrade().as_deref() else {
+            continue;
+        };
+        *delta.entry(life_time.start).or_default() += size as isize;
+        *delta.entry(life_time.end + 1).or_default() -= size as isize;
+    }
+    let mut current = 0;
+    let mut ans = (0, 0);
+    for (i, d) in delta {
+        current += d;
+        if current as usize > ans.0 {
+            ans = (current as usize, i)
+        }
+    }
+    ans
+}
+
+fn json_str(s: Option<&str>) -> String {
+    let Some(s) = s else {
+        return "null".into();
+    };
+    let mut ans = String::with_capacity(s.len() + 2);
+    ans.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ans.push_str("\\\""),
+            '\\' => ans.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(ans, "\\u{:04x}", c as u32).unwrap(),
+            c => ans.push(c),
+        }
+    }
+    ans.push('"');
+    ans
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,307 @@
+use super::{AllocError, MemRangeMap, key_weak::KeyWeak};
+use crate::{BlobLifeTime, Info};
+use std::{iter::zip, ops::Range};
+
+/// 偏移计算策略。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub enum Strategy {
+    /// 按生命周期事件顺序分配，每次从空闲表中取最合适的空闲区间。
+    #[default]
+    BestFit,
+    /// 从大到小依次放置，每个块放在与之生命周期重叠的块之间最合适的空隙中。
+    GreedyBySize,
+    /// 区间图着色，生命周期不重叠的块共享同一个槽位，槽位依次排列。
+    IntervalColoring,
+    /// 以 [`Strategy::GreedyBySize`] 的结果为初始解，块数不超过 [`Strategy::SEARCH_LIMIT`] 时穷举放置顺序求最优解，
+    /// 否则直接使用初始解，是否穷举记录在 [`StrategyReport::searched`](crate::StrategyReport::searched) 中。
+    GreedyWithSearch,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 4] = [
+        Self::BestFit,
+        Self::GreedyBySize,
+        Self::IntervalColoring,
+        Self::GreedyWithSearch,
+    ];
+
+    /// [`Strategy::GreedyWithSearch`] 穷举的最大块数。
+    pub const SEARCH_LIMIT: usize = 10;
+}
+
+/// 参与离线放置的块。
+pub(super) struct Item<T> {
+    pub blob: KeyWeak<Info<T>>,
+    pub life_time: Range<usize>,
+    pub size: usize,
+    /// 对齐后的长度。
+    pub len: usize,
+}
+
+impl<T> Item<T> {
+    pub fn new(blt: BlobLifeTime<T>, alignment: usize) -> Self {
+        let BlobLifeTime { blob, life_time } = blt;
+        let Some(&Info::Internal(size, _)) = blob.upgrade().as_deref() else {
+            unreachable!()
+        };
+        Self {
+            blob,
+            life_time,
+            size,
+            len: size.div_ceil(alignment) * alignment,
+        }
+    }
+
+    /// 生命周期是否重叠，生命周期是闭区间。
+    fn overlaps(&self, other: &Self) -> bool {
+        self.life_time.start <= other.life_time.end && other.life_time.start <= self.life_time.end
+    }
+}
+
+/// 离线放置结果，每个块的偏移与 `items` 一一对应。
+pub(super) type Offsets = Vec<usize>;
+
+pub(super) fn greedy_by_size<T>(items: &[Item<T>]) -> Offsets {
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(items[i].len));
+
+    let mut offsets = vec![usize::MAX; items.len()];
+    let mut placed = Vec::<usize>::with_capacity(items.len());
+    for i in order {
+        let item = &items[i];
+        // 与之重叠的已放置块按偏移排序，找最小的足够大的空隙
+        let mut neighbors = placed
+            .iter()
+            .filter(|&&j| items[j].overlaps(item))
+            .map(|&j| offsets[j]..offsets[j] + items[j].len)
+            .collect::<Vec<_>>();
+        neighbors.sort_unstable_by_key(|r| r.start);
+
+        let mut best = None::<(usize, usize)>;
+        let mut cursor = 0;
+        for r in &neighbors {
+            if r.start >= cursor + item.len {
+                let gap = r.start - cursor;
+                if best.is_none_or(|(_, len)| gap < len) {
+                    best = Some((cursor, gap))
+                }
+            }
+            cursor = cursor.max(r.end)
+        }
+        offsets[i] = best.map_or(cursor, |(off, _)| off);
+        placed.push(i)
+    }
+    offsets
+}
+
+pub(super) fn interval_coloring<T>(items: &[Item<T>]) -> Offsets {
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| (items[i].life_time.start, std::cmp::Reverse(items[i].len)));
+
+    // 每个槽位记录其长度和最后一次被占用到的节点
+    let mut slots = Vec::<(usize, usize)>::new();
+    let mut colors = vec![0; items.len()];
+    for i in order {
+        let item = &items[i];
+        let free = (0..slots.len()).filter(|&s| slots[s].1 < item.life_time.start);
+        // 优先选能容纳的最小槽位，否则扩大最大的空闲槽位
+        let fit = free
+            .clone()
+            .filter(|&s| slots[s].0 >= item.len)
+            .min_by_key(|&s| slots[s].0);
+        let color = match fit.or_else(|| free.max_by_key(|&s| slots[s].0)) {
+            Some(s) => s,
+            None => {
+                slots.push((0, 0));
+                slots.len() - 1
+            }
+        };
+        let slot = &mut slots[color];
+        slot.0 = slot.0.max(item.len);
+        slot.1 = item.life_time.end;
+        colors[i] = color
+    }
+
+    let mut bases = Vec::with_capacity(slots.len());
+    let mut base = 0;
+    for (len, _) in slots {
+        bases.push(base);
+        base += len
+    }
+    colors.into_iter().map(|c| bases[c]).collect()
+}
+
+pub(super) fn greedy_with_search<T>(items: &[Item<T>]) -> Offsets {
+    let greedy = greedy_by_size(items);
+    if items.len() > Strategy::SEARCH_LIMIT {
+        return greedy;
+    }
+
+    let mut search = Search {
+        items,
+        lower_bound: lower_bound(items),
+        best_peak: peak(items, &greedy),
+        best: greedy,
+        offsets: vec![usize::MAX; items.len()],
+        placed: Vec::with_capacity(items.len()),
+    };
+    search.dfs(0);
+    search.best
+}
+
+/// 任意时刻同时存在的块的总大小的最大值，是峰值的下界。
+pub(super) fn lower_bound<T>(items: &[Item<T>]) -> usize {
+    let mut events = items
+        .iter()
+        .flat_map(|item| {
+            [
+                (item.life_time.start, 0, item.size),
+                (item.life_time.end, 1, item.size),
+            ]
+        })
+        .collect::<Vec<_>>();
+    // 同一节点先分配后释放
+    events.sort_unstable();
+    let mut current = 0usize;
+    let mut ans = 0;
+    for (_, op, len) in events {
+        if op == 0 {
+            current += len;
+            ans = ans.max(current)
+        } else {
+            current -= len
+        }
+    }
+    ans
+}
+
+fn peak<T>(items: &[Item<T>], offsets: &[usize]) -> usize {
+    items
+        .iter()
+        .zip(offsets)
+        .map(|(item, off)| off + item.size)
+        .max()
+        .unwrap_or(0)
+}
+
+/// 分支限界搜索。
+///
+/// 按某个顺序依次把块放在最低的可行位置，最优解中的块按偏移排序后用这种方式放置不会更差，
+/// 因此穷举所有顺序可以得到最优解。
+struct Search<'a, T> {
+    items: &'a [Item<T>],
+    lower_bound: usize,
+    best_peak: usize,
+    best: Offsets,
+    offsets: Offsets,
+    placed: Vec<usize>,
+}
+
+impl<T> Search<'_, T> {
+    fn dfs(&mut self, peak: usize) {
+        if self.best_peak == self.lower_bound {
+            return;
+        }
+        if self.placed.len() == self.items.len() {
+            if peak < self.best_peak {
+                self.best_peak = peak;
+                self.best.clone_from(&self.offsets)
+            }
+            return;
+        }
+        for i in 0..self.items.len() {
+            if self.offsets[i] != usize::MAX {
+                continue;
+            }
+            let off = self.lowest(i);
+            let peak = peak.max(off + self.items[i].size);
+            if peak >= self.best_peak {
+                continue;
+            }
+            self.offsets[i] = off;
+            self.placed.push(i);
+            self.dfs(peak);
+            self.placed.pop();
+            self.offsets[i] = usize::MAX
+        }
+    }
+
+    /// 块 `i` 不与已放置的块冲突的最低位置。
+    fn lowest(&self, i: usize) -> usize {
+        let item = &self.items[i];
+        let mut neighbors = self
+            .placed
+            .iter()
+            .filter(|&&j| self.items[j].overlaps(item))
+            .map(|&j| self.offsets[j]..self.offsets[j] + self.items[j].len)
+            .collect::<Vec<_>>();
+        neighbors.sort_unstable_by_key(|r| r.start);
+
+        let mut cursor = 0;
+        for r in neighbors {
+            if r.start >= cursor + item.len {
+                break;
+            }
+            cursor = cursor.max(r.end)
+        }
+        cursor
+    }
+}
+
+/// 找到最早一个超出 `max_size` 的块。
+pub(super) fn overflow<T>(
+    items: &[Item<T>],
+    offsets: &[usize],
+    max_size: usize,
+) -> Option<AllocError<T>> {
+    let (item, _) = zip(items, offsets)
+        .filter(|(item, off)| item.len > 0 && *off + item.size > max_size)
+        .min_by_key(|(item, _)| item.life_time.start)?;
+    let peak = zip(items, offsets)
+        .filter(|(other, off)| {
+            other.len > 0
+                && other.life_time.start <= item.life_time.start
+                && *off + other.size <= max_size
+        })
+        .map(|(other, off)| off + other.size)
+        .max()
+        .unwrap_or(0);
+    // 离线放置没有分配顺序，取此块存在期间占用最多的节点
+    let i_node = (item.life_time.start..=item.life_time.end)
+        .max_by_key(|&k| {
+            let live = items
+                .iter()
+                .filter(|other| other.life_time.start <= k && k <= other.life_time.end)
+                .map(|other| other.size)
+                .sum::<usize>();
+            (live, std::cmp::Reverse(k))
+        })
+        .unwrap();
+    Some(AllocError {
+        blob: item.blob.clone(),
+        size: item.size,
+        i_node,
+        peak,
+    })
+}
+
+/// 将离线放置结果转换为块到地址区间的映射。
+pub(super) fn collect<T>(items: Vec<Item<T>>, offsets: Offsets) -> MemRangeMap<T> {
+    let mut range = super::EMPTY_RANGE;
+    let map = items
+        .into_iter()
+        .zip(offsets)
+        .map(|(item, off)| {
+            let Item {
+                blob, size, len, ..
+            } = item;
+            if len == 0 {
+                return (blob, usize::MAX..usize::MAX);
+            }
+            range.start = range.start.min(off);
+            range.end = range.end.max(off + size);
+            (blob, off..off + size)
+        })
+        .collect();
+    MemRangeMap { range, map }
+}
@@ -0,0 +1,405 @@
+use crate::{AllocError, Edge, Graph, Info, KeyWeak, MemRangeMap, Node, Pool, PoolConfig, op};
+use graph::{EditGraph, NodeRef, TopoBuilder};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::zip,
+    rc::Rc,
+};
+use tensor::Tensor;
+
+/// [`Graph::fit`] 对图做的修改。
+#[derive(Default, Debug)]
+pub struct FitPlan {
+    /// 被重新计算的节点。
+    pub recomputed: Vec<String>,
+    /// 卸载到主机的块的大小，序号即 `copy-out` 和 `copy-in` 节点名字中的序号。
+    pub offloaded: Vec<usize>,
+}
+
+/// 在空闲期间释放的块。
+struct Victim<T> {
+    blob: Rc<Info<T>>,
+    size: usize,
+    /// 产生此块的节点和边。
+    producer: (usize, usize),
+    /// 空闲期间前最后一次使用和之后第一次使用。
+    gap: (usize, usize),
+}
+
+impl<T> Graph<T> {
+    /// 使用 `config` 分配 `pool` 中的块空间不足时，选择在失败节点处空闲的块重新计算或卸载到 `host` 存储池，
+    /// 直到能在 `config.max_size` 内完成分配。
+    ///
+    /// 每次选择跨过失败节点且不被其使用的最大的块，之后的使用改为新的块：
+    ///
+    /// - 生产者满足 `cheap`、其输入在下次使用时仍然存在且期间没有被改写，且之后只以生产者输出的布局使用，
+    ///   则在下次使用前重新执行生产者，之后的使用改为其输出；
+    /// - 否则在空闲前插入 `copy-out` 节点将块复制到 `host` 中的一个块，在下次使用前插入 `copy-in` 节点复制回来，
+    ///   `copy-in` 对之后使用的每种布局各有一个输出。
+    ///
+    /// 失败时已做的修改会保留，返回最后一次分配的错误。
+    pub fn fit(
+        &mut self,
+        pool: Pool,
+        config: PoolConfig,
+        host: Pool,
+        mut cheap: impl FnMut(&Node) -> bool,
+    ) -> Result<(MemRangeMap<T>, FitPlan), AllocError<T>> {
+        let mut plan = FitPlan::default();
+        // 已经处理过的块不再选择，避免在同一个节点处反复交换
+        let mut relieved = HashSet::new();
+        loop {
+            let err = match self.plan(Some(pool), config) {
+                Ok(map) => return Ok((map, plan)),
+                Err(err) => err,
+            };
+            let Some(victim) = self.victim(err.i_node, pool, &relieved) else {
+                return Err(err);
+            };
+            let (p, e) = victim.producer;
+            let recompute = self.0.topo.iter().nth(p).unwrap().outputs.len() == 1
+                && cheap(&self.0.nodes[p])
+                && self.stable_inputs(p, victim.gap.1);
+
+            let mut graph = EditGraph::from(std::mem::replace(&mut self.0, empty()));
+            let uses = later_uses(&graph, &victim.blob, victim.gap.1);
+            let recompute = recompute
+                && uses
+                    .iter()
+                    .all(|(e_, _)| same_layout(graph.edge(*e_), graph.edge(e)));
+            let new = Rc::new(Info::Internal(victim.size, pool));
+            relieved.insert(KeyWeak::from(&new));
+            let order = if recompute {
+                plan.recomputed
+                    .push(graph.node(p).unwrap().node.name.clone());
+                recompute_blob(&mut graph, victim, new, uses)
+            } else {
+                let host = Rc::new(Info::Internal(victim.size, host));
+                plan.offloaded.push(victim.size);
+                offload_blob(
+                    &mut graph,
+                    victim,
+                    new,
+                    host,
+                    uses,
+                    plan.offloaded.len() - 1,
+                )
+            };
+            self.0 = graph.into_graph_in(order).unwrap()
+        }
+    }
+
+    /// 选择跨过节点 `i` 且不被其使用的 `pool` 中最大的块。
+    ///
+    /// 只选择由一个节点完整产生、不是全图输入输出的非空块，跳过 `relieved` 中的块。
+    fn victim(
+        &self,
+        i: usize,
+        pool: Pool,
+        relieved: &HashSet<KeyWeak<Info<T>>>,
+    ) -> Option<Victim<T>> {
+        let Self(graph::Graph { topo, nodes, edges }) = self;
+
+        let mut uses = HashMap::<KeyWeak<Info<T>>, Vec<usize>>::new();
+        let mut producers = HashMap::<KeyWeak<Info<T>>, Vec<(usize, usize)>>::new();
+        for (k, (topo, node)) in zip(topo.iter(), nodes).enumerate() {
+            if node.value.name == "empty" {
+                continue;
+            }
+            let NodeRef { inputs, outputs } = topo;
+            for &e in inputs {
+                uses.entry(edges[e].get().into()).or_default().push(k)
+            }
+            for e in outputs {
+                uses.entry(edges[e].get().into()).or_default().push(k);
+                producers
+                    .entry(edges[e].get().into())
+                    .or_default()
+                    .push((k, e))
+            }
+        }
+        let pinned = topo
+            .global_inputs()
+            .chain(topo.global_outputs().iter().copied())
+            .map(|e| KeyWeak::from(edges[e].get()))
+            .collect::<Vec<_>>();
+
+        uses.into_iter()
+            .filter(|(blob, _)| !pinned.contains(blob) && !relieved.contains(blob))
+            .filter_map(|(blob, uses)| {
+                let &[producer] = &*producers.remove(&blob)? else {
+                    return None;
+                };
+                let (_, e) = producer;
+                let size = whole(&edges[e], pool).filter(|&size| size > 0)?;
+                let before = uses.iter().copied().filter(|&k| k < i).max()?;
+                let after = uses.iter().copied().filter(|&k| k > i).min()?;
+                if uses.contains(&i) {
+                    return None;
+                }
+                Some(Victim {
+                    blob: edges[e].get().clone(),
+                    size,
+                    producer,
+                    gap: (before, after),
+                })
+            })
+            .max_by_key(|v| (v.size, v.gap.1 - v.gap.0))
+    }
+
+    /// 节点 `p` 的所有输入在节点 `i` 处是否仍然存在，且从 `p` 到 `i` 之前没有节点向其中写入。
+    ///
+    /// 原地化后其他节点可能把输出写到输入的块中，此时在 `i` 处重新执行 `p` 得到的结果不同。
+    fn stable_inputs(&self, p: usize, i: usize) -> bool {
+        let life_time = self
+            .blob_lifetime()
+            .into_iter()
+            .map(|blt| (blt.blob, blt.life_time))
+            .collect::<HashMap<_, _>>();
+        let graph::Graph { topo, nodes, edges } = &self.0;
+        let NodeRef { inputs, .. } = topo.iter().nth(p).unwrap();
+        let alive = inputs.iter().all(|&e| {
+            // 不在表中的是外部存储
+            life_time
+                .get(&edges[e].get().into())
+                .is_none_or(|lt| lt.start <= i && i <= lt.end)
+        });
+        let blobs = inputs
+            .iter()
+            .map(|&e| KeyWeak::from(edges[e].get()))
+            .collect::<HashSet<_>>();
+        let written = zip(topo.iter(), nodes)
+            .take(i)
+            .skip(p)
+            .filter(|(_, node)| node.value.name != "empty")
+            .any(|(NodeRef { mut outputs, .. }, _)| {
+                outputs.any(|e| blobs.contains(&edges[e].get().into()))
+            });
+        alive && !written
+    }
+}
+
+/// 边是否完整覆盖了 `pool` 中的内部块，返回块的大小。
+fn whole<T>(edge: &Edge<T>, pool: Pool) -> Option<usize> {
+    let &Info::Internal(size, p) = &**edge.get() else {
+        return None;
+    };
+    if p != pool {
+        return None;
+    }
+    let full = Tensor::<_, 2>::from_dim_slice(edge.dt(), edge.shape());
+    let (a, b) = (full.layout(), edge.layout());
+    (*full.get() == size && a.strides() == b.strides() && a.offset() == b.offset()).then_some(size)
+}
+
+/// 节点 `from` 及之后对 `blob` 块的使用，按布局分组，每组为 `(第一次使用的边, [(节点, 输入槽位)..])`。
+fn later_uses<T>(
+    graph: &EditGraph<Node, Edge<T>>,
+    blob: &Rc<Info<T>>,
+    from: usize,
+) -> Vec<(usize, Vec<(usize, usize)>)> {
+    let mut uses = Vec::<(usize, Vec<_>)>::new();
+    for i in from..graph.n_node() {
+        let Some(node) = graph.node(i) else { continue };
+        for (slot, &e) in node.inputs.iter().enumerate() {
+            if !Rc::ptr_eq(graph.edge(e).get(), blob) {
+                continue;
+            }
+            match uses
+                .iter_mut()
+                .find(|(e_, _)| same_layout(graph.edge(*e_), graph.edge(e)))
+            {
+                Some((_, slots)) => slots.push((i, slot)),
+                None => uses.push((e, vec![(i, slot)])),
+            }
+        }
+    }
+    uses
+}
+
+/// 在空闲期间之后重新执行生产者，之后的使用改为其输出，返回节点顺序。
+fn recompute_blob<T>(
+    graph: &mut EditGraph<Node, Edge<T>>,
+    victim: Victim<T>,
+    new: Rc<Info<T>>,
+    uses: Vec<(usize, Vec<(usize, usize)>)>,
+) -> Vec<usize> {
+    let Victim {
+        producer: (p, e),
+        gap: (_, after),
+        ..
+    } = victim;
+
+    let node = graph.node(p).unwrap();
+    let mut copy = node.node.clone();
+    copy.name = format!("{}-recompute", copy.name);
+    let inputs = node.inputs.clone();
+    let output = graph.add_edge(with_blob(graph.edge(e), &new));
+    let i = graph.insert(copy, inputs, vec![output]);
+    // 之后只以生产者输出的布局使用
+    for (_, slots) in uses {
+        redirect(graph, slots, output)
+    }
+
+    reorder(graph.n_node() - 1, [(after, i, true)])
+}
+
+/// 在空闲期间前将块复制到主机上的 `host` 块，之后复制回新的块，之后的使用改为 `copy-in` 的输出，返回节点顺序。
+fn offload_blob<T>(
+    graph: &mut EditGraph<Node, Edge<T>>,
+    victim: Victim<T>,
+    new: Rc<Info<T>>,
+    host: Rc<Info<T>>,
+    uses: Vec<(usize, Vec<(usize, usize)>)>,
+    slot: usize,
+) -> Vec<usize> {
+    let Victim {
+        producer: (_, e),
+        gap: (before, after),
+        ..
+    } = victim;
+
+    let saved = graph.add_edge(with_blob(graph.edge(e), &host));
+    let outputs = uses
+        .iter()
+        .map(|&(e, _)| graph.add_edge(with_blob(graph.edge(e), &new)))
+        .collect::<Vec<_>>();
+    let copy_out = graph.insert(op::copy_out(slot), vec![e], vec![saved]);
+    let copy_in = graph.insert(
+        op::copy_in(slot, outputs.len()),
+        vec![saved],
+        outputs.clone(),
+    );
+    for ((_, slots), output) in zip(uses, outputs) {
+        redirect(graph, slots, output)
+    }
+
+    reorder(
+        graph.n_node() - 2,
+        [(before, copy_out, false), (after, copy_in, true)],
+    )
+}
+
+/// 将每个 `(节点, 输入槽位)` 改为使用边 `e`。
+fn redirect<T>(graph: &mut EditGraph<Node, Edge<T>>, slots: Vec<(usize, usize)>, e: usize) {
+    for (i, slot) in slots {
+        graph.set_input(i, slot, e);
+    }
+}
+
+/// 两条边的数据类型和布局是否相同。
+fn same_layout<T>(a: &Edge<T>, b: &Edge<T>) -> bool {
+    let (la, lb) = (a.layout(), b.layout());
+    a.dt() == b.dt()
+        && a.shape() == b.shape()
+        && la.strides() == lb.strides()
+        && la.offset() == lb.offset()
+}
+
+/// 与 `edge` 布局相同，存储在 `blob` 中的边。
+fn with_blob<T>(edge: &Edge<T>, blob: &Rc<Info<T>>) -> Edge<T> {
+    edge.as_ref().map(|_| blob.clone())
+}
+
+/// 原有的 `n` 个节点保持顺序，将新节点插入到指定节点之前或之后。
+fn reorder<const N: usize>(n: usize, inserts: [(usize, usize, bool); N]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(n + N);
+    for i in 0..n {
+        order.extend(
+            inserts
+                .iter()
+                .filter(|&&(at, _, before)| at == i && before)
+                .map(|&(_, new, _)| new),
+        );
+        order.push(i);
+        order.extend(
+            inserts
+                .iter()
+                .filter(|&&(at, _, before)| at == i && !before)
+                .map(|&(_, new, _)| new),
+        )
+    }
+    order
+}
+
+/// 用于临时替换图的空图。
+pub(crate) fn empty<N, E>() -> graph::Graph<N, E> {
+    graph::Graph {
+        topo: TopoBuilder::new(0).build([]).unwrap(),
+        nodes: Box::new([]),
+        edges: Box::new([]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Graph, Info, Node, Pool, PoolConfig, Strategy};
+    use exec::Operator;
+    use graph::{TopoBuilder, TopoInput::*};
+    use tensor::{Tensor, digit_layout::types};
+
+    /// x = load(g), u = norm(x), v = big(u), w = big(v), x2 = scale(x), y = add(w, u, x)；
+    /// `inplace` 时 scale 原地写入 x。
+    fn graph(inplace: bool) -> Graph<()> {
+        let mut topo = TopoBuilder::new(1);
+        let x = topo.push([Edge(0)], 1).unwrap().start;
+        let u = topo.push([Edge(x)], 1).unwrap().start;
+        let v = topo.push([Edge(u)], 1).unwrap().start;
+        let w = topo.push([Edge(v)], 1).unwrap().start;
+        let x2 = topo.push([Edge(x)], 1).unwrap().start;
+        let y = topo.push([Edge(w), Edge(u), Edge(x)], 1).unwrap().start;
+        let topo = topo.build([y, x2]).unwrap();
+
+        let nodes = ["load", "norm", "big", "big", "scale", "add"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, op)| Node {
+                name: format!("n{i}"),
+                value: Operator {
+                    name: op.into(),
+                    arg: None,
+                },
+            });
+        let edges = [2, 2, 4, 8, 8, 2, 4].map(|n| {
+            Tensor::from_dim_slice(types::F32, [n, 16])
+                .map(|size| Info::Internal(size, Pool::DEFAULT))
+        });
+        let mut graph = Graph::new(topo, nodes, edges);
+        if inplace {
+            graph.0.edges[x2] = graph.0.edges[x].clone()
+        }
+        graph
+    }
+
+    fn fit(inplace: bool) -> super::FitPlan {
+        let config = PoolConfig {
+            strategy: Strategy::GreedyBySize,
+            max_size: 1280,
+            alignment: 64,
+        };
+        let mut graph = graph(inplace);
+        assert!(graph.plan(Some(Pool::DEFAULT), config).is_err());
+        let (map, plan) = graph
+            .fit(Pool::DEFAULT, config, Pool(1), |node| {
+                node.value.name == "norm"
+            })
+            .unwrap();
+        assert!(map.range.len() <= config.max_size);
+        plan
+    }
+
+    #[test]
+    fn recompute() {
+        let plan = fit(false);
+        assert_eq!(plan.recomputed, ["n1"]);
+        assert!(plan.offloaded.is_empty());
+    }
+
+    #[test]
+    fn offload_overwritten_input() {
+        // norm 的输入在重新计算前被 scale 改写，只能卸载
+        let plan = fit(true);
+        assert!(plan.recomputed.is_empty());
+        assert_eq!(plan.offloaded, [256]);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{BlobLifeTime, Edge, Graph, Info, KeyWeak, Node};
+use graph::NodeRef;
+use std::{collections::HashMap, iter::zip};
+
+impl<T> Graph<T> {
+    /// 算子原地化（内存块合并）。
+    ///
+    /// `alias` 返回节点声明的原地计算关系，每项 `(output, input)` 表示第 `output` 个输出可以复用第 `input` 个输入的存储，
+    /// 同一个输出按声明的顺序尝试。满足以下条件时输出块合并到输入块：
+    ///
+    /// - 输入输出都是同一个存储池中的内部存储，且输入块不是全图输入；
+    /// - 输入块在此节点之后不再被使用，输出块在此节点之前没有被使用；
+    /// - 输入输出边的数据类型和布局完全相同，且输入块不小于输出块。
+    ///
+    /// 返回合并的块数。
+    pub fn inplace(&mut self, mut alias: impl FnMut(&Node) -> Vec<(usize, usize)>) -> usize {
+        let mut life_time = self
+            .blob_lifetime()
+            .into_iter()
+            .map(|BlobLifeTime { blob, life_time }| (blob, life_time))
+            .collect::<HashMap<_, _>>();
+
+        let Self(graph::Graph { topo, nodes, edges }) = self;
+        let pinned = topo
+            .global_inputs()
+            .map(|i| KeyWeak::from(edges[i].get()))
+            .collect::<Vec<_>>();
+
+        let mut count = 0;
+        for (i, (topo, node)) in zip(topo.iter(), &**nodes).enumerate() {
+            if node.value.name == "empty" {
+                continue;
+            }
+            let NodeRef { inputs, outputs } = topo;
+            for (output, input) in alias(node) {
+                let (Some(&input), Some(output)) = (inputs.get(input), outputs.clone().nth(output))
+                else {
+                    continue;
+                };
+                let src = KeyWeak::from(edges[input].get());
+                let dst = KeyWeak::from(edges[output].get());
+                if src == dst || pinned.contains(&src) {
+                    continue;
+                }
+                let (Some(src_life), Some(dst_life)) = (life_time.get(&src), life_time.get(&dst))
+                else {
+                    // 不在表中的是外部存储
+                    continue;
+                };
+                if src_life.end != i
+                    || dst_life.start != i
+                    || !compatible(&edges[input], &edges[output])
+                {
+                    continue;
+                }
+                // 合并生命周期，并让输出块的所有视图改用输入块
+                let merged = src_life.start..dst_life.end;
+                life_time.remove(&dst);
+                life_time.insert(src, merged);
+                let blob = edges[input].get().clone();
+                for edge in edges.iter_mut() {
+                    if KeyWeak::from(edge.get()) == dst {
+                        *edge.get_mut() = blob.clone()
+                    }
+                }
+                count += 1
+            }
+        }
+        count
+    }
+}
+
+/// 输出能否原地写入输入的位置。
+fn compatible<T>(input: &Edge<T>, output: &Edge<T>) -> bool {
+    let (&Info::Internal(src, src_pool), &Info::Internal(dst, dst_pool)) =
+        (&**input.get(), &**output.get())
+    else {
+        return false;
+    };
+    let (a, b) = (input.layout(), output.layout());
+    src >= dst
+        && src_pool == dst_pool
+        && input.dt() == output.dt()
+        && a.shape() == b.shape()
+        && a.strides() == b.strides()
+        && a.offset() == b.offset()
+}
@@ -0,0 +1,102 @@
+use crate::{
+    Edge, Graph, Info, Node, Pool,
+    op::{self, Rearrange, Slot},
+};
+use graph::NodeRef;
+use std::{collections::HashMap, iter::zip, rc::Rc};
+use tensor::Tensor;
+
+/// 算子对一个输入的布局要求。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct LayoutReq {
+    /// 最后一维连续。
+    pub contiguous_last: bool,
+    /// 整体行优先连续。
+    pub row_major: bool,
+    /// 起始地址偏移对齐到的字节数，0 表示不要求。
+    pub alignment: usize,
+}
+
+impl LayoutReq {
+    /// 接受任意布局。
+    pub const ANY: Self = Self {
+        contiguous_last: false,
+        row_major: false,
+        alignment: 0,
+    };
+    /// 要求最后一维连续。
+    pub const CONTIGUOUS_LAST: Self = Self {
+        contiguous_last: true,
+        ..Self::ANY
+    };
+    /// 要求整体行优先连续。
+    pub const ROW_MAJOR: Self = Self {
+        row_major: true,
+        ..Self::ANY
+    };
+
+    /// 边的布局是否满足要求，长度为 1 的维度不检查步长。
+    pub fn check<T>(&self, edge: &Edge<T>) -> bool {
+        let full = Tensor::<_, 2>::from_dim_slice(edge.dt(), edge.shape());
+        let (layout, full) = (edge.layout(), full.layout());
+        let dims =
+            zip(layout.shape(), zip(full.strides(), layout.strides())).filter(|&(&d, _)| d != 1);
+        let last = dims.clone().next_back();
+        (!self.contiguous_last || last.is_none_or(|(_, (a, b))| a == b))
+            && (!self.row_major || dims.clone().all(|(_, (a, b))| a == b))
+            && (self.alignment == 0 || layout.offset() % self.alignment as isize == 0)
+    }
+}
+
+impl<T> Graph<T> {
+    /// 在布局不满足要求的输入前插入 rearrange，复制到一个新的连续块中。
+    ///
+    /// `req` 返回节点对每个输入的要求，缺少的输入没有要求。新的块与原来的块在同一个存储池中。
+    /// 多个节点对同一条边有相同的要求时共用一次复制。
+    ///
+    /// 返回插入的 rearrange 数。
+    pub fn rearrange_inputs(&mut self, mut req: impl FnMut(&Node) -> Vec<LayoutReq>) -> usize {
+        let graph::Graph { topo, nodes, edges } = &self.0;
+
+        let mut rearranges = Vec::new();
+        let mut copies = HashMap::new();
+        for (i, (topo, node)) in zip(topo.iter(), &**nodes).enumerate() {
+            if node.value.name == "empty" {
+                continue;
+            }
+            let NodeRef { inputs, .. } = topo;
+            for (k, (&e, req)) in zip(inputs, req(node)).enumerate() {
+                let edge = &edges[e];
+                if req.check(edge) {
+                    continue;
+                }
+                let pool = match &**edge.get() {
+                    &Info::Internal(_, pool) => pool,
+                    Info::External(_) => Pool::DEFAULT,
+                };
+                let view = copies
+                    .entry((e, req))
+                    .or_insert_with(|| {
+                        Tensor::from_dim_slice(edge.dt(), edge.shape())
+                            .map(|size| Rc::new(Info::Internal(size, pool)))
+                    })
+                    .clone();
+                rearranges.push((
+                    i,
+                    Rearrange {
+                        slot: Slot::Input(k),
+                        edge: e,
+                        view,
+                    },
+                ))
+            }
+        }
+
+        let count = copies.len();
+        if count > 0 {
+            let graph = std::mem::replace(&mut self.0, crate::fit::empty());
+            self.0 = op::rearrange(graph, rearranges)
+        }
+        count
+    }
+}
@@ -1,12 +1,21 @@
 mod analyze;
+mod fit;
+mod inplace;
+mod layout;
 mod op;
+mod pool;
 
 use graph::GraphTopo;
 use std::{iter::zip, rc::Rc};
 use tensor::Tensor;
 
-pub use analyze::{Action, BlobLifeTime, KeyWeak, MemRangeMap, print_lifetime};
+pub use analyze::{
+    Action, AllocError, BlobLifeTime, BlobReport, KeyWeak, MemRangeMap, MemReport, PoolConfig,
+    PoolError, Strategy, StrategyReport, print_lifetime,
+};
 pub use exec::{Exec, Node, Operator};
+pub use fit::FitPlan;
+pub use layout::LayoutReq;
 
 #[repr(transparent)]
 pub struct Graph<T>(pub graph::Graph<Node, Edge<T>>);
@@ -14,10 +23,25 @@ pub struct Graph<T>(pub graph::Graph<Node, Edge<T>>);
 pub type Edge<T> = Tensor<Rc<Info<T>>, 2>;
 
 pub enum Info<T> {
-    Internal(usize),
+    /// 内部存储的大小和所在的存储池。
+    ///
+    /// 引入存储池之前的 `Internal(size)` 对应 `Internal(size, Pool::DEFAULT)`。
+    Internal(usize, Pool),
     External(External<T>),
 }
 
+/// 存储池编号，不同存储池的地址分别计算。
+///
+/// 编号的含义由使用者决定，例如设备内存、锁页主存或不同的卡。
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[repr(transparent)]
+pub struct Pool(pub usize);
+
+impl Pool {
+    /// 默认存储池，锁定形状时所有内部存储都在此存储池中。
+    pub const DEFAULT: Self = Self(0);
+}
+
 #[derive(Clone)]
 pub struct External<T> {
     pub name: String,
@@ -35,17 +59,25 @@ impl<T> Graph<T> {
             .into_iter()
             .map(|t| t.map(Rc::new))
             .collect::<Box<_>>();
-        for (node, topo) in zip(&mut nodes, topo.iter()) {
-            match &*node.value.name {
+        let mut rearranges = Vec::new();
+        for (i, (node, topo)) in zip(&mut nodes, topo.iter()).enumerate() {
+            let list = match &*node.value.name {
                 "split" => op::split(node, topo, &mut edges),
                 "tile" => op::tile(node, topo, &mut edges),
                 "merge" => op::merge(node, topo, &mut edges),
                 "transpose" => op::transpose(node, topo, &mut edges),
                 "concat" => op::concat(node, topo, &mut edges),
-                _ => {}
-            }
+                _ => continue,
+            };
+            rearranges.extend(list.into_iter().map(|r| (i, r)))
+        }
+        let graph = graph::Graph { topo, nodes, edges };
+        // 视图算子涉及外部存储或不连续的输入时插入 rearrange
+        if rearranges.is_empty() {
+            Self(graph)
+        } else {
+            Self(op::rearrange(graph, rearranges))
         }
-        Self(graph::Graph { topo, nodes, edges })
     }
 
     pub fn lower<U>(
@@ -57,7 +89,7 @@ impl<T> Graph<T> {
         let edges = edges
             .into_iter()
             .map(|tensor| match &**tensor.get() {
-                Info::Internal(_) => tensor.as_ref().map(|_| internal(tensor.get().into())),
+                Info::Internal(..) => tensor.as_ref().map(|_| internal(tensor.get().into())),
                 Info::External(External { item, .. }) => tensor.as_ref().map(|_| external(item)),
             })
             .collect();
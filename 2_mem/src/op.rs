@@ -1,9 +1,45 @@
 ﻿use crate::{Edge, Info, Node};
 use arg::Arg;
 use exec::Operator;
-use graph::NodeRef;
+use graph::{EditGraph, NodeRef};
+use std::{collections::HashMap, rc::Rc};
 
-pub(crate) fn split<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+/// 节点的一条边不能直接使用时，需要插入 rearrange 节点在代替它的视图和原来的边之间复制数据。
+///
+/// 例如视图算子涉及外部存储或不连续的输入，或者算子不接受输入的布局。
+pub(crate) struct Rearrange<T> {
+    /// 被替换的边在节点中的位置。
+    pub slot: Slot,
+    /// 被替换的边。
+    pub edge: usize,
+    /// 在节点中代替原来的边的视图。
+    pub view: Edge<T>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Slot {
+    /// 节点的输入，rearrange 在节点之前将原来的边复制到视图。
+    Input(usize),
+    /// 节点的输出，rearrange 在节点之后将视图复制到原来的边。
+    Output(usize),
+}
+
+/// 用视图替换边 `e`，`e` 是外部存储时保留原来的边，记录需要插入的 rearrange。
+fn replace<T>(edges: &mut [Edge<T>], e: usize, slot: Slot, view: Edge<T>) -> Option<Rearrange<T>> {
+    match &**edges[e].get() {
+        Info::Internal(..) => {
+            edges[e] = view;
+            None
+        }
+        Info::External(_) => Some(Rearrange {
+            slot,
+            edge: e,
+            view,
+        }),
+    }
+}
+
+pub(crate) fn split<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) -> Vec<Rearrange<T>> {
     let NodeRef { inputs, outputs } = topo;
     // split 应该只有一个输入
     let &[input] = inputs else { unreachable!() };
@@ -15,25 +51,25 @@ pub(crate) fn split<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
     let axis = arg["axis"].to_usize();
     // 计算步长变换
     let mut start = 0;
-    for output in outputs {
-        let output = &mut edges[output];
-        let part = output.shape()[axis];
-        // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
-        assert!(matches!(&**output.get(), Info::Internal(_)));
+    let mut rearranges = Vec::new();
+    for (i, output) in outputs.enumerate() {
+        let part = edges[output].shape()[axis];
         // 用 slice 实现 split，并替换原来的边
-        *output = input
+        let view = input
             .clone()
             .transform(|layout| layout.slice(axis, start, 1, part));
+        rearranges.extend(replace(edges, output, Slot::Output(i), view));
         start += part
     }
     // 算子擦除
     node.value = Operator {
         name: "empty".to_string(),
         arg: None,
-    }
+    };
+    rearranges
 }
 
-pub(crate) fn tile<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+pub(crate) fn tile<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) -> Vec<Rearrange<T>> {
     let NodeRef { inputs, outputs } = topo;
     // tile 应该只有一个输入
     let &[input] = inputs else { unreachable!() };
@@ -58,23 +94,18 @@ pub(crate) fn tile<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
         .collect::<Vec<_>>();
     // 计算步长变换
     assert_eq!(outputs.len(), 1); // tile 应该只有一个输出
-    for output in outputs {
-        let output = &mut edges[output];
-        // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
-        assert!(matches!(&**output.get(), Info::Internal(_)));
-        // 用 tile_be 实现，并替换原来的边
-        *output = input
-            .clone()
-            .transform(|layout| layout.tile_be(axis, &tile));
-    }
+    // 用 tile_be 实现，并替换原来的边
+    let view = input.transform(|layout| layout.tile_be(axis, &tile));
+    let rearranges = replace(edges, outputs.start, Slot::Output(0), view);
     // 算子擦除
     node.value = Operator {
         name: "empty".to_string(),
         arg: None,
-    }
+    };
+    rearranges.into_iter().collect()
 }
 
-pub(crate) fn merge<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+pub(crate) fn merge<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) -> Vec<Rearrange<T>> {
     let NodeRef { inputs, outputs } = topo;
     // merge 应该只有一个输入
     let &[input] = inputs else { unreachable!() };
@@ -85,28 +116,40 @@ pub(crate) fn merge<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
     };
     let start = arg["start"].to_usize();
     let len = arg["len"].to_usize();
-    if let Some(layout) = input.layout().merge_be(start, len) {
-        // 可以合并则 input 连续，做算子擦除
-        // 计算步长变换
-        assert_eq!(outputs.len(), 1); // merge 应该只有一个输出
-        for output in outputs {
-            let output = &mut edges[output];
-            // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
-            assert!(matches!(&**output.get(), Info::Internal(_)));
-            // 用 merge_be 实现，并替换原来的边
-            *output = input.clone().transform(|_| layout.clone());
+    assert_eq!(outputs.len(), 1); // merge 应该只有一个输出
+    // 算子擦除
+    node.value = Operator {
+        name: "empty".to_string(),
+        arg: None,
+    };
+    match input.layout().merge_be(start, len) {
+        // 可以合并则 input 连续，用 merge_be 实现，并替换原来的边
+        Some(layout) => {
+            let view = input.transform(|_| layout);
+            replace(edges, outputs.start, Slot::Output(0), view)
+                .into_iter()
+                .collect()
+        }
+        // 不能合并则 input 不连续，先用 rearrange 把 input 复制到 output 按 input 形状拆开的视图中
+        None => {
+            let tile = input.shape()[start..][..len].to_vec();
+            let view = edges[outputs.start]
+                .clone()
+                .transform(|layout| layout.tile_be(start, &tile));
+            vec![Rearrange {
+                slot: Slot::Input(0),
+                edge: inputs[0],
+                view,
+            }]
         }
-        // 算子擦除
-        node.value = Operator {
-            name: "empty".to_string(),
-            arg: None,
-        };
     }
-
-    // 不能合并则 input 不连续，不做擦除; 在 llama.cu 里 merge 会换成 rearrange 把 input 变连续
 }
 
-pub(crate) fn transpose<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+pub(crate) fn transpose<T>(
+    node: &mut Node,
+    topo: NodeRef,
+    edges: &mut [Edge<T>],
+) -> Vec<Rearrange<T>> {
     let NodeRef { inputs, outputs } = topo;
     // transpose 应该只有一个输入
     let &[input] = inputs else { unreachable!() };
@@ -130,21 +173,22 @@ pub(crate) fn transpose<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]
         .collect::<Vec<_>>();
     // 计算步长变换
     assert_eq!(outputs.len(), 1); // transpose 应该只有一个输出
-    for output in outputs {
-        let output = &mut edges[output];
-        // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
-        assert!(matches!(&**output.get(), Info::Internal(_)));
-        // 用 transpose 实现，并替换原来的边
-        *output = input.clone().transform(|layout| layout.transpose(&perm));
-    }
+    // 用 transpose 实现，并替换原来的边
+    let view = input.transform(|layout| layout.transpose(&perm));
+    let rearranges = replace(edges, outputs.start, Slot::Output(0), view);
     // 算子擦除
     node.value = Operator {
         name: "empty".to_string(),
         arg: None,
-    }
+    };
+    rearranges.into_iter().collect()
 }
 
-pub(crate) fn concat<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+pub(crate) fn concat<T>(
+    node: &mut Node,
+    topo: NodeRef,
+    edges: &mut [Edge<T>],
+) -> Vec<Rearrange<T>> {
     let NodeRef { inputs, outputs } = topo;
     // concat 应该只有一个输出
     assert_eq!(outputs.len(), 1);
@@ -156,20 +200,100 @@ pub(crate) fn concat<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
     let axis = axis as usize;
     // 计算步长变换
     let mut start = 0;
-    for &input in inputs {
-        let input = &mut edges[input];
-        let part = input.shape()[axis];
-        // 暂时不支持 input 是外部的，因为外部 input 需要添加 rearrange kernel
-        assert!(matches!(&**input.get(), Info::Internal(_)));
+    let mut rearranges = Vec::new();
+    for (i, &input) in inputs.iter().enumerate() {
+        let part = edges[input].shape()[axis];
         // 用 slice 实现 split，并替换原来的边
-        *input = output
+        let view = output
             .clone()
             .transform(|layout| layout.slice(axis, start, 1, part));
+        rearranges.extend(replace(edges, input, Slot::Input(i), view));
         start += part
     }
     // 算子擦除
     node.value = Operator {
         name: "empty".to_string(),
         arg: None,
+    };
+    rearranges
+}
+
+/// 卸载第 `slot` 个块时，在空闲期间前将块复制到主机的 `copy-out` 节点。
+pub(crate) fn copy_out(slot: usize) -> Node {
+    Node {
+        name: format!("offload:copy-out-{slot}"),
+        value: Operator {
+            name: "copy-out".to_string(),
+            arg: None,
+        },
+    }
+}
+
+/// 卸载第 `slot` 个块时，在下次使用前从主机复制回来的 `copy-in` 节点，对之后使用的 `n` 种布局各有一个输出。
+pub(crate) fn copy_in(slot: usize, n: usize) -> Node {
+    Node {
+        name: format!("offload:copy-in-{slot}"),
+        value: Operator {
+            name: "copy-in".to_string(),
+            arg: Some(Arg::int(n)),
+        },
+    }
+}
+
+/// 插入 rearrange 节点，每项 `(i, rearrange)` 对应第 `i` 个节点上的一条边。
+///
+/// 节点中被替换的边改为视图，rearrange 紧挨着放在节点之前或之后。
+/// 多个节点的输入把同一条边复制到同一块存储的视图时，只在第一个节点之前复制一次。
+pub(crate) fn rearrange<T>(
+    graph: graph::Graph<Node, Edge<T>>,
+    rearranges: Vec<(usize, Rearrange<T>)>,
+) -> graph::Graph<Node, Edge<T>> {
+    let n_node = graph.nodes.len();
+    let mut graph = EditGraph::from(graph);
+    let mut before = vec![Vec::new(); n_node];
+    let mut after = vec![Vec::new(); n_node];
+    let mut copies = HashMap::new();
+    for (i, Rearrange { slot, edge, view }) in rearranges {
+        // 复用已经插入的复制
+        let key = (edge, Rc::as_ptr(view.get()));
+        if let (Slot::Input(k), Some(&view)) = (slot, copies.get(&key)) {
+            graph.set_input(i, k, view);
+            continue;
+        }
+        let view = graph.add_edge(view);
+        if let Slot::Input(_) = slot {
+            copies.insert(key, view);
+        }
+        let (inputs, outputs, k) = match slot {
+            Slot::Input(k) => {
+                graph.set_input(i, k, view);
+                (vec![edge], vec![view], k)
+            }
+            Slot::Output(k) => {
+                graph.set_output(i, k, view);
+                (vec![view], vec![edge], k)
+            }
+        };
+        let node = Node {
+            name: format!("{}-rearrange-{k}", graph.node(i).unwrap().node.name),
+            value: Operator {
+                name: "rearrange".to_string(),
+                arg: None,
+            },
+        };
+        let new = graph.insert(node, inputs, outputs);
+        match slot {
+            Slot::Input(_) => before[i].push(new),
+            Slot::Output(_) => after[i].push(new),
+        }
     }
+    let order = (0..n_node)
+        .flat_map(|i| {
+            let mut order = std::mem::take(&mut before[i]);
+            order.push(i);
+            order.append(&mut after[i]);
+            order
+        })
+        .collect::<Vec<_>>();
+    graph.into_graph_in(order).unwrap()
 }
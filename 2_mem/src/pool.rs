@@ -0,0 +1,61 @@
+use crate::{Graph, Info, KeyWeak, Node, Pool};
+use graph::NodeRef;
+use std::{collections::HashMap, iter::zip, rc::Rc};
+
+impl<T> Graph<T> {
+    /// 图中用到的存储池，按编号排序。
+    pub fn pools(&self) -> Vec<Pool> {
+        let mut ans = self
+            .0
+            .edges
+            .iter()
+            .filter_map(|e| match **e.get() {
+                Info::Internal(_, pool) => Some(pool),
+                Info::External(_) => None,
+            })
+            .collect::<Vec<_>>();
+        ans.sort_unstable();
+        ans.dedup();
+        ans
+    }
+
+    /// 为每个内部块指定存储池，`pool` 的参数为产生此块的节点，全图输入没有生产者。
+    ///
+    /// 返回存储池改变的块数。
+    pub fn assign_pools(&mut self, mut pool: impl FnMut(Option<&Node>) -> Pool) -> usize {
+        let Self(graph::Graph { topo, nodes, edges }) = self;
+
+        let mut producers = HashMap::new();
+        for (topo, node) in zip(topo.iter(), &**nodes) {
+            if node.value.name == "empty" {
+                continue;
+            }
+            let NodeRef { outputs, .. } = topo;
+            for e in outputs {
+                producers
+                    .entry(KeyWeak::from(edges[e].get()))
+                    .or_insert(node);
+            }
+        }
+
+        // 存储池改变的块替换为新的块，同一个块的所有视图一起替换
+        let mut moved = HashMap::<KeyWeak<Info<T>>, Option<Rc<Info<T>>>>::new();
+        for edge in edges.iter_mut() {
+            let &Info::Internal(size, old) = &**edge.get() else {
+                continue;
+            };
+            let key = KeyWeak::from(edge.get());
+            let new = moved
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let new = pool(producers.get(&key).copied());
+                    (new != old).then(|| Rc::new(Info::Internal(size, new)))
+                })
+                .clone();
+            if let Some(new) = new {
+                *edge.get_mut() = new
+            }
+        }
+        moved.values().filter(|new| new.is_some()).count()
+    }
+}
@@ -4,8 +4,8 @@ mod model;
 
 use gguf::{GGufModel, map_files};
 use ggus::ggml_quants::digit_layout::types;
-use nn::{Dim, Exec, GraphBuilder, Node, OpInfo, TensorMeta, op};
-use std::{collections::BTreeSet, iter::zip, time::Instant};
+use nn::{BlockInput, Dim, DynamicRegion, Exec, GraphBuilder, Node, OpInfo, TensorMeta, op};
+use std::time::Instant;
 
 // cargo run --release -- ../TinyStory-5M-v0.0-F32.gguf
 fn main() {
@@ -16,77 +16,111 @@ fn main() {
     let mut gguf = GGufModel::read(maps.iter().map(|x| &**x));
     let model = model::init(&mut gguf);
     // let model = model::init_mamba(&mut gguf);
+    // let model = model::init_mamba2(&mut gguf);
+    // let model = model::init_rwkv6(&mut gguf);
+    // let model = model::init_rwkv7(&mut gguf);
     timer.push("init");
 
     // 构造计算图
-    let graph = GraphBuilder::default()
+    let mut builder = GraphBuilder::default();
+    builder
         .register_op("embedding", op::embedding::Embedding)
         .register_op("rms-norm", op::normalization::RmsNorm)
         .register_op("layer-norm", op::normalization::LayerNorm)
+        .register_op("group-norm", op::normalization::GroupNorm)
+        .register_op("gated-rms-norm", op::normalization::GatedRmsNorm)
         .register_op("attention", op::attention::Attention)
+        .register_op("conv", op::conv::Conv)
         .register_op("mamba-causal-conv1d", op::mamba::CausalConv1d)
         .register_op("mamba-selective-scan", op::mamba::SelectiveScan)
+        .register_op("mamba-ssd-scan", op::mamba::SsdScan)
+        .register_op("moe-gate", op::moe::MoeGate)
+        .register_op("moe-linear", op::moe::MoeLinear)
+        .register_op("moe-combine", op::moe::MoeCombine)
         .register_op("rwkv-time-mix", op::rwkv::RWKVTimeMix)
         .register_op("rwkv-channel-mix", op::rwkv::RWKVChannelMix)
+        .register_op("rwkv-lerp", op::rwkv::RWKVLerp)
+        .register_op("rwkv6-ddlerp", op::rwkv::RWKV6DdLerp)
+        .register_op("rwkv6-wkv", op::rwkv::RWKV6Wkv)
+        .register_op("rwkv7-v-mix", op::rwkv::RWKV7VMix)
+        .register_op("rwkv7-time-mix", op::rwkv::RWKV7TimeMix)
         .register_op("split", op::split::Split)
         .register_op("tile", op::tile::Tile)
         .register_op("merge", op::merge::Merge)
+        .register_op("rearrange", op::rearrange::Rearrange)
+        .register_op("copy-out", op::offload::CopyOut)
+        .register_op("copy-in", op::offload::CopyIn)
         .register_op("swiglu", op::activation::SwiGLU)
         .register_op("silu", op::activation::SiLU)
         .register_op("gelu", op::activation::GeLU)
+        .register_op("tanh", op::activation::Tanh)
+        .register_op("sigmoid", op::activation::Sigmoid)
+        .register_op("relu-sq", op::activation::ReluSq)
         .register_op("linear", op::linear::Linear)
         .register_op("rope", op::rope::Rope)
         .register_op("concat", op::concat::Concat)
+        .register_op("add", op::add::Add)
         .register_op("element-mul", op::element_mul::ElementMul)
-        .build(
-            model,
-            [
-                TensorMeta::new(types::U32, [Dim::from("n_tok")]),
-                TensorMeta::new(types::U32, [Dim::from("n_tok")]),
-                TensorMeta::new(types::U32, [Dim::from("n_out")]),
-            ],
-        )
-        .unwrap();
-    timer.push("build");
-    // 动态性分析
-    let mut start: Option<(String, usize)> = None;
-    let mut variables = BTreeSet::new();
-    for (i, (topo, node)) in zip(graph.0.topo.iter(), &graph.0.nodes).enumerate() {
-        let mut vars = BTreeSet::new();
-        for i in topo.inputs.iter().cloned().chain(topo.outputs) {
-            for d in graph.0.edges[i].meta.shape.iter() {
-                d.append_variables(&mut vars)
-            }
-        }
-        match start.as_ref() {
-            Some((name, start_)) => {
-                if variables != vars {
-                    println!(
-                        "{start_:>3}..{i:>3} {name:>30}..{:<30} {variables:?}",
-                        node.name
-                    );
-                    start = Some((node.name.clone(), i));
-                    variables = vars;
-                }
-            }
-            None => {
-                start = Some((node.name.clone(), i));
-                variables = vars;
-            }
+        .register_op("rms-norm-linear", op::fused::RmsNormLinear)
+        .register_op("linear-swiglu", op::fused::LinearSwiGLU)
+        .register_op("qkv-rope", op::fused::QkvRope)
+        .register_op("attention-output", op::fused::AttentionOutput);
+    // 输入依次为 tokens、块声明的输入和 out_idx，Mamba 和 RWKV 没有 pos
+    let mut inputs = vec![TensorMeta::new(types::U32, [Dim::from("n_tok")])];
+    for input in model.block_inputs() {
+        match input {
+            BlockInput::Pos => inputs.push(TensorMeta::new(types::U32, [Dim::from("n_tok")])),
+            BlockInput::Memory => unreachable!("decoder-only models have no memory"),
         }
     }
-    if let Some((name, start)) = start {
-        println!("{start:>3}..    {name:>30}..{:<30} {variables:?}", "")
+    if model.output_head.is_some() {
+        inputs.push(TensorMeta::new(types::U32, [Dim::from("n_out")]))
+    }
+    let graph = builder.build(model, inputs).unwrap();
+    timer.push("build");
+    // 图变换
+    let graph = builder
+        .pass_manager()
+        .add(nn::pass::Cse)
+        .add(nn::pass::Fusion::ALL)
+        .add(nn::pass::Dce)
+        .run(graph)
+        .unwrap();
+    timer.push("pass");
+    // 动态性分析
+    for DynamicRegion {
+        nodes,
+        variables,
+        namespace,
+    } in graph.dynamic_regions()
+    {
+        println!(
+            "{:>3}..{:>3} {namespace:<30} {variables:?}",
+            nodes.start, nodes.end
+        )
     }
     println!();
     // 锁定形状
-    let graph = graph.lower(&[("n_tok", 5), ("n_out", 1)].into(), |t| {
+    let mut graph = graph.lower(&[("n_tok", 5), ("n_out", 1)].into(), |t| {
         gguf.tensors[&*t].as_ref()
     });
     timer.push("fix shape");
+    // 算子原地化
+    let n_inplace = builder.inplace(&mut graph);
+    timer.push("inplace");
+    println!("inplace: {n_inplace} blobs merged");
+    // 布局检查
+    let n_rearrange = builder.rearrange_inputs(&mut graph);
+    timer.push("rearrange");
+    println!("rearrange: {n_rearrange} inputs copied");
     // 分配空间
-    let mem_range_map = graph.mem_range_map(20 << 30, 512);
+    let report = graph.compare_strategies(512);
+    print!("{report}");
+    let mem_range_map = graph
+        .mem_range_map_with(report.best(), 20 << 30, 512)
+        .unwrap();
     timer.push("alloc");
+    print!("{}", graph.mem_report(None, &mem_range_map));
     // 锁定地址
     let mut _workspace = vec![0u8; mem_range_map.range.len()];
     let exec = graph
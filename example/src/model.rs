@@ -5,7 +5,7 @@
 };
 use ggus::GGufMetaMapExt;
 use nn::{SelectiveSSM, Tensor};
-use tensor::digit_layout::types;
+use tensor::digit_layout::{DigitLayout, types};
 
 pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
     let arch = meta![gguf => general_architecture];
@@ -48,6 +48,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                 weight: "token_embd.weight".to_string(),
             },
             wpe: None,
+            norm: None,
         },
         blks: (0..nblk)
             .map(|iblk| {
@@ -111,6 +112,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                             format!("blk.{iblk}.attn_output.weight"),
                             None,
                         ),
+                        causal: true,
                     },
                     ::nn::Normalization {
                         d,
@@ -164,6 +166,8 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
 
 #[allow(dead_code)]
 pub fn init_mamba(gguf: &mut GGufModel) -> nn::Mamba<String> {
+    let arch = meta![gguf => general_architecture];
+    let ssm = |key: &str| format!("{arch}.ssm.{key}");
     let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
     let nblk = meta![gguf => llm_block_count];
     let d = meta![gguf => llm_embedding_length];
@@ -172,10 +176,10 @@ pub fn init_mamba(gguf: &mut GGufModel) -> nn::Mamba<String> {
     let dt_norm = gguf.tensors["output_norm.weight"].dt();
     let dt_linear = gguf.tensors["blk.0.ssm_in.weight"].dt();
 
-    let d_kernel = 4;
-    let d_inner = 5120;
-    let d_state = 16;
-    let dt_rank = 160; // ggus todo: mamba.ssm.
+    let d_kernel = meta![gguf => (usize) &ssm("conv_kernel")];
+    let d_inner = meta![gguf => (usize) &ssm("inner_size")];
+    let d_state = meta![gguf => (usize) &ssm("state_size")];
+    let dt_rank = meta![gguf => (usize) &ssm("time_step_rank")];
 
     ::nn::Mamba {
         embedding: ::nn::Embedding {
@@ -186,6 +190,7 @@ pub fn init_mamba(gguf: &mut GGufModel) -> nn::Mamba<String> {
                 weight: "token_embd.weight".to_string(),
             },
             wpe: None,
+            norm: None,
         },
         blks: (0..nblk)
             .map(|iblk| ::nn::MambaBlock {
@@ -234,6 +239,7 @@ pub fn init_mamba(gguf: &mut GGufModel) -> nn::Mamba<String> {
                         },
                         a: format!("blk.{iblk}.ssm_a"),
                         d: format!("blk.{iblk}.ssm_d"),
+                        state: None,
                     },
                     out_proj: nn::Linear {
                         dt: dt_linear,
@@ -269,6 +275,336 @@ pub fn init_mamba(gguf: &mut GGufModel) -> nn::Mamba<String> {
     }
 }
 
+#[allow(dead_code)]
+pub fn init_mamba2(gguf: &mut GGufModel) -> nn::Mamba2<String> {
+    let arch = meta![gguf => general_architecture];
+    let ssm = |key: &str| format!("{arch}.ssm.{key}");
+    let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
+    let nblk = meta![gguf => llm_block_count];
+    let d = meta![gguf => llm_embedding_length];
+    let epsilon = meta![gguf => llm_attention_layer_norm_rms_epsilon; 1e-5];
+    let d_kernel = meta![gguf => (usize) &ssm("conv_kernel")];
+    let d_inner = meta![gguf => (usize) &ssm("inner_size")];
+    let d_state = meta![gguf => (usize) &ssm("state_size")];
+    let n_head = meta![gguf => (usize) &ssm("time_step_rank")];
+    let n_group = meta![gguf => (usize) &ssm("group_count"); 1];
+    let dt_embd = gguf.tensors["token_embd.weight"].dt();
+    let dt_norm = gguf.tensors["output_norm.weight"].dt();
+
+    let blks = (0..nblk)
+        .map(|iblk| {
+            let name = |name: &str| format!("blk.{iblk}.{name}");
+            let dt_conv = gguf.tensors[&*name("ssm_conv1d.weight")].dt();
+            ::nn::Mamba2Block {
+                mamba_norm: ::nn::Normalization {
+                    d,
+                    epsilon: epsilon as _,
+                    items: ::nn::NormType::RmsNorm {
+                        dt: dt_norm,
+                        scale: name("attn_norm.weight"),
+                    },
+                },
+                mamba_mixer: ::nn::Mamba2Mixer {
+                    d_inner,
+                    d_state,
+                    n_group,
+                    n_head,
+                    in_proj: linear(gguf, &name("ssm_in")),
+                    causal_conv1d: nn::CausalConv1d::new(
+                        dt_conv,
+                        name("ssm_conv1d.weight"),
+                        name("ssm_conv1d.bias"),
+                        d_kernel,
+                        d_inner + 2 * n_group * d_state,
+                    ),
+                    act: nn::Activation::SiLU,
+                    a: param(reshape(gguf, &name("ssm_a"), &[n_head]), gguf),
+                    d: param(reshape(gguf, &name("ssm_d"), &[n_head]), gguf),
+                    dt_bias: param(name("ssm_dt.bias"), gguf),
+                    norm_epsilon: epsilon as _,
+                    norm_scale: param(reshape(gguf, &name("ssm_norm.weight"), &[d_inner]), gguf),
+                    out_proj: linear(gguf, &name("ssm_out")),
+                    state: None,
+                },
+            }
+        })
+        .collect();
+
+    ::nn::Mamba2 {
+        embedding: ::nn::Embedding {
+            dt: dt_embd,
+            d,
+            wte: ::nn::Table {
+                row: nvoc,
+                weight: "token_embd.weight".to_string(),
+            },
+            wpe: None,
+            norm: None,
+        },
+        blks,
+        output_head: Some(::nn::OutputHead {
+            out_norm: ::nn::Normalization {
+                d,
+                epsilon: epsilon as _,
+                items: ::nn::NormType::RmsNorm {
+                    dt: dt_norm,
+                    scale: "output_norm.weight".into(),
+                },
+            },
+            lm_head: linear(
+                gguf,
+                if gguf.tensors.contains_key("output.weight") {
+                    "output"
+                } else {
+                    "token_embd"
+                },
+            ),
+        }),
+    }
+}
+
+#[allow(dead_code)]
+pub fn init_rwkv6(gguf: &mut GGufModel) -> nn::RWKV6<String> {
+    let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
+    let nblk = meta![gguf => llm_block_count];
+    let d = meta![gguf => llm_embedding_length];
+    let epsilon = meta![gguf => llm_attention_layer_norm_epsilon; 1e-5];
+    let head_size = meta![gguf => (usize) "rwkv6.wkv.head_size"];
+    let lerp_rank = meta![gguf => (usize) "rwkv6.time_mix_extra_dim"];
+    let dt_embd = gguf.tensors["token_embd.weight"].dt();
+
+    let blks = (0..nblk)
+        .map(|iblk| {
+            let name = |name: &str| format!("blk.{iblk}.{name}.weight");
+            let dt = gguf.tensors[&*name("time_mix_lerp_x")].dt();
+            let d_attn = gguf.tensors[&*name("time_mix_receptance")].shape()[0];
+            let nh = d_attn / head_size;
+            let decay = format!("blk.{iblk}.time_mix_decay");
+            ::nn::RWKV6Block {
+                ln1: layer_norm(gguf, &format!("blk.{iblk}.attn_norm"), epsilon),
+                time_mix: ::nn::RWKV6TimeMix {
+                    dt,
+                    d,
+                    head_size,
+                    lerp_rank,
+                    lerp_x: reshape(gguf, &name("time_mix_lerp_x"), &[1, d]),
+                    lerp_w1: name("time_mix_w1"),
+                    lerp_w2: name("time_mix_w2"),
+                    lerp_fused: reshape(gguf, &name("time_mix_lerp_fused"), &[5, d]),
+                    decay: ::nn::Lora {
+                        down: linear(gguf, &format!("{decay}_w1")),
+                        act: Some(::nn::Activation::Tanh),
+                        up: ::nn::Linear {
+                            bias: Some((dt, reshape(gguf, &name("time_mix_decay"), &[d_attn]))),
+                            ..linear(gguf, &format!("{decay}_w2"))
+                        },
+                    },
+                    first: reshape(gguf, &name("time_mix_first"), &[nh, head_size]),
+                    r: linear(gguf, &format!("blk.{iblk}.time_mix_receptance")),
+                    k: linear(gguf, &format!("blk.{iblk}.time_mix_key")),
+                    v: linear(gguf, &format!("blk.{iblk}.time_mix_value")),
+                    g: linear(gguf, &format!("blk.{iblk}.time_mix_gate")),
+                    ln_x: group_norm(gguf, &format!("blk.{iblk}.time_mix_ln"), nh),
+                    output: linear(gguf, &format!("blk.{iblk}.time_mix_output")),
+                    shift_state: None,
+                    wkv_state: None,
+                },
+                ln2: layer_norm(gguf, &format!("blk.{iblk}.attn_norm_2"), epsilon),
+                channel_mix: ::nn::RWKV6ChannelMix {
+                    dt,
+                    d,
+                    lerp_k: reshape(gguf, &name("channel_mix_lerp_k"), &[1, d]),
+                    lerp_r: reshape(gguf, &name("channel_mix_lerp_r"), &[1, d]),
+                    k: linear(gguf, &format!("blk.{iblk}.channel_mix_key")),
+                    v: linear(gguf, &format!("blk.{iblk}.channel_mix_value")),
+                    r: linear(gguf, &format!("blk.{iblk}.channel_mix_receptance")),
+                    state: None,
+                },
+            }
+        })
+        .collect();
+
+    ::nn::RWKV6 {
+        embedding: ::nn::Embedding {
+            dt: dt_embd,
+            d,
+            wte: ::nn::Table {
+                row: nvoc,
+                weight: "token_embd.weight".to_string(),
+            },
+            wpe: None,
+            norm: Some(layer_norm(gguf, "token_embd_norm", epsilon)),
+        },
+        blks,
+        output_head: Some(rwkv_output_head(gguf, epsilon)),
+    }
+}
+
+#[allow(dead_code)]
+pub fn init_rwkv7(gguf: &mut GGufModel) -> nn::RWKV7<String> {
+    let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
+    let nblk = meta![gguf => llm_block_count];
+    let d = meta![gguf => llm_embedding_length];
+    let epsilon = meta![gguf => llm_attention_layer_norm_epsilon; 1e-5];
+    let head_size = meta![gguf => (usize) "rwkv7.wkv.head_size"];
+    let dt_embd = gguf.tensors["token_embd.weight"].dt();
+
+    let blks = (0..nblk)
+        .map(|iblk| {
+            let name = |name: &str| format!("blk.{iblk}.{name}.weight");
+            let dt = gguf.tensors[&*name("time_mix_lerp_fused")].dt();
+            let d_attn = gguf.tensors[&*name("time_mix_receptance")].shape()[0];
+            let nh = d_attn / head_size;
+            let lerp_fused = reshape(gguf, &name("time_mix_lerp_fused"), &[6, d]);
+            // 低秩投影 {prefix}1、{prefix}2，{prefix}0 为上投影的偏置
+            let mut lora = |prefix: &str, act, bias: bool| ::nn::Lora {
+                down: linear(gguf, &format!("blk.{iblk}.{prefix}1")),
+                act,
+                up: ::nn::Linear {
+                    bias: bias.then(|| {
+                        let bias = reshape(gguf, &name(&format!("{prefix}0")), &[d_attn]);
+                        (gguf.tensors[&*bias].dt(), bias)
+                    }),
+                    ..linear(gguf, &format!("blk.{iblk}.{prefix}2"))
+                },
+            };
+            let w = lora("time_mix_w", Some(::nn::Activation::Tanh), true);
+            let a = lora("time_mix_a", None, true);
+            let v_mix = (iblk > 0).then(|| lora("time_mix_v", None, true));
+            let g = lora("time_mix_g", Some(::nn::Activation::Sigmoid), false);
+            let time_mix = ::nn::RWKV7TimeMix {
+                dt,
+                d,
+                head_size,
+                lerp_fused,
+                r: linear(gguf, &format!("blk.{iblk}.time_mix_receptance")),
+                k: linear(gguf, &format!("blk.{iblk}.time_mix_key")),
+                v: linear(gguf, &format!("blk.{iblk}.time_mix_value")),
+                w,
+                a,
+                v_mix,
+                g,
+                k_k: reshape(gguf, &name("time_mix_k_k"), &[d_attn]),
+                k_a: reshape(gguf, &name("time_mix_k_a"), &[d_attn]),
+                r_k: reshape(gguf, &name("time_mix_r_k"), &[nh, head_size]),
+                ln_x: ::nn::LnX {
+                    epsilon: GROUP_NORM_EPSILON,
+                    scale: format!("blk.{iblk}.time_mix_ln.weight"),
+                    bias: format!("blk.{iblk}.time_mix_ln.bias"),
+                },
+                output: linear(gguf, &format!("blk.{iblk}.time_mix_output")),
+                shift_state: None,
+                wkv_state: None,
+            };
+            ::nn::RWKV7Block {
+                ln1: layer_norm(gguf, &format!("blk.{iblk}.attn_norm"), epsilon),
+                time_mix,
+                ln2: layer_norm(gguf, &format!("blk.{iblk}.attn_norm_2"), epsilon),
+                channel_mix: ::nn::RWKV7ChannelMix {
+                    dt,
+                    d,
+                    lerp_k: reshape(gguf, &name("channel_mix_lerp_k"), &[1, d]),
+                    k: linear(gguf, &format!("blk.{iblk}.channel_mix_key")),
+                    v: linear(gguf, &format!("blk.{iblk}.channel_mix_value")),
+                    state: None,
+                },
+            }
+        })
+        .collect();
+
+    ::nn::RWKV7 {
+        embedding: ::nn::Embedding {
+            dt: dt_embd,
+            d,
+            wte: ::nn::Table {
+                row: nvoc,
+                weight: "token_embd.weight".to_string(),
+            },
+            wpe: None,
+            norm: Some(layer_norm(gguf, "token_embd_norm", epsilon)),
+        },
+        blks,
+        output_head: Some(rwkv_output_head(gguf, epsilon)),
+    }
+}
+
+/// llama.cpp 中 RWKV 按头分组归一化的 epsilon。
+const GROUP_NORM_EPSILON: f64 = 64e-5;
+
+fn rwkv_output_head(gguf: &GGufModel, epsilon: f32) -> ::nn::OutputHead<String> {
+    let lm_head = if gguf.tensors.contains_key("output.weight") {
+        "output"
+    } else {
+        "token_embd"
+    };
+    ::nn::OutputHead {
+        out_norm: layer_norm(gguf, "output_norm", epsilon),
+        lm_head: linear(gguf, lm_head),
+    }
+}
+
+/// 按 `{name}.weight` 和 `{name}.bias` 构造层归一化。
+fn layer_norm(gguf: &GGufModel, name: &str, epsilon: f32) -> ::nn::Normalization<String> {
+    let scale = format!("{name}.weight");
+    let bias = format!("{name}.bias");
+    ::nn::Normalization {
+        d: gguf.tensors[&*scale].shape()[0],
+        epsilon: epsilon as _,
+        items: ::nn::NormType::LayerNorm {
+            dt_scale: gguf.tensors[&*scale].dt(),
+            scale,
+            dt_bias: gguf.tensors[&*bias].dt(),
+            bias,
+        },
+    }
+}
+
+/// 按 `{name}.weight` 和 `{name}.bias` 构造分为 `groups` 组的组归一化。
+fn group_norm(gguf: &GGufModel, name: &str, groups: usize) -> ::nn::Normalization<String> {
+    let scale = format!("{name}.weight");
+    let bias = format!("{name}.bias");
+    ::nn::Normalization {
+        d: gguf.tensors[&*scale].shape()[0],
+        epsilon: GROUP_NORM_EPSILON,
+        items: ::nn::NormType::GroupNorm {
+            groups,
+            dt_scale: gguf.tensors[&*scale].dt(),
+            scale,
+            dt_bias: gguf.tensors[&*bias].dt(),
+            bias,
+        },
+    }
+}
+
+/// 按 `{name}.weight` 的形状构造没有偏置的线性层。
+fn linear(gguf: &GGufModel, name: &str) -> ::nn::Linear<String> {
+    let weight = format!("{name}.weight");
+    let tensor = &gguf.tensors[&*weight];
+    let &[r, c] = tensor.shape() else {
+        panic!("{weight} is not a matrix")
+    };
+    ::nn::Linear::new(tensor.dt(), [r, c], weight, None)
+}
+
+/// 附上张量 `name` 自己的数据类型。
+fn param(name: String, gguf: &GGufModel) -> (DigitLayout, String) {
+    (gguf.tensors[&*name].dt(), name)
+}
+
+/// 将连续存储的张量 `name` 改为 `shape` 形状，用于去掉 GGuf 中长度为 1 的维度，返回张量名。
+fn reshape(gguf: &mut GGufModel, name: &str, shape: &[usize]) -> String {
+    let (key, tensor) = gguf.tensors.remove_entry(name).unwrap();
+    assert!(tensor.is_contiguous(), "{name} is not contiguous");
+    let dt = tensor.dt();
+    let data = tensor.take();
+    let tensor = Tensor::from_dim_slice(dt, shape).map(|len| {
+        assert_eq!(len, data.len(), "{name} cannot be reshaped to {shape:?}");
+        data
+    });
+    gguf.tensors.insert(key, tensor);
+    name.to_string()
+}
+
 /// 构造 sin cos 表张量
 fn build_sin_cos<'a, const N: usize>(
     nctx: usize,